@@ -0,0 +1,14 @@
+//! Параллельные примитивы и вспомогательные типы без зависимости от `std`.
+//!
+//! С feature `std` (по умолчанию) fork-join выполняется через rayon.
+//! Без нее библиотека собирается под `no_std` + `alloc`, а fork-join выполняется
+//! последовательно (см. [parallel_primitives::join]). Бенчмарк и сортировки
+//! живут в бинарнике и требуют `std`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "handwritten")]
+pub mod parallel_primitives;
+pub mod utils;
@@ -1,66 +1,368 @@
-mod parallel_primitives;
+#[cfg(feature = "peak-memory")]
+mod peak_alloc;
+mod pivot;
 mod sort;
-mod utils;
 
+#[cfg(feature = "handwritten")]
+use cw1_quicksort_rust::parallel_primitives;
+use cw1_quicksort_rust::utils;
+
+#[cfg(feature = "handwritten")]
+use crate::parallel_primitives::par_eq;
+#[cfg(feature = "peak-memory")]
+use crate::peak_alloc::PeakAlloc;
+#[cfg(feature = "rayon-primitives")]
+use crate::sort::rayon_parallel_quicksort;
+#[cfg(feature = "handwritten")]
 use crate::sort::{
     parallel_quicksort_3par_memcpy, parallel_quicksort_par_memcpy, parallel_quicksort_seq_memcpy,
-    rayon_parallel_quicksort, sequential_quicksort, simple_parallel_quicksort,
 };
+use crate::sort::{sequential_quicksort, simple_parallel_quicksort, SortAlgorithm};
 use crate::utils::Random;
 use rayon::prelude::ParallelSliceMut;
 use std::time::{Duration, Instant};
 
 fn main() {
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    });
+
     rayon::ThreadPoolBuilder::new()
-        .num_threads(4)
+        .num_threads(args.threads)
         .build_global()
         .unwrap();
 
-    bench_sort("sequential", sequential_quicksort);
-    bench_sort(
-        "parallel (with polylog span and handmade primitives, sequential memcpy)",
-        parallel_quicksort_seq_memcpy,
-    );
-    bench_sort(
-        "parallel (with polylog span and handmade primitives, 3-parallel memcpy)",
-        parallel_quicksort_3par_memcpy,
-    );
-    bench_sort(
-        "parallel (with truly polylog span and handmade primitives, parallel memcpy)",
-        parallel_quicksort_par_memcpy,
-    );
-    bench_sort("parallel (rayon primitives)", rayon_parallel_quicksort);
-    bench_sort(
+    let mut sorters: Vec<(SortAlgorithm, &str, Sorter)> = vec![(
+        SortAlgorithm::Sequential,
+        "sequential",
+        sequential_quicksort,
+    )];
+    #[cfg(feature = "handwritten")]
+    sorters.extend_from_slice(&[
+        (
+            SortAlgorithm::PolylogSeqMemcpy,
+            "parallel (with polylog span and handmade primitives, sequential memcpy)",
+            parallel_quicksort_seq_memcpy,
+        ),
+        (
+            SortAlgorithm::Polylog3ParMemcpy,
+            "parallel (with polylog span and handmade primitives, 3-parallel memcpy)",
+            parallel_quicksort_3par_memcpy,
+        ),
+        (
+            SortAlgorithm::PolylogParMemcpy,
+            "parallel (with truly polylog span and handmade primitives, parallel memcpy)",
+            parallel_quicksort_par_memcpy,
+        ),
+    ]);
+    #[cfg(feature = "rayon-primitives")]
+    sorters.push((
+        SortAlgorithm::Rayon,
+        "parallel (rayon primitives)",
+        rayon_parallel_quicksort,
+    ));
+    sorters.push((
+        SortAlgorithm::SimpleParallel,
         "parallel (simple but with big span)",
         simple_parallel_quicksort,
-    );
+    ));
+    sorters.retain(|&(algorithm, _, _)| args.is_selected(algorithm));
+
+    let mut results = vec![];
+    for distribution in DISTRIBUTIONS {
+        // Все сортировки получают одинаковые входные данные
+        let input = BenchInput::generate(args.size, distribution.generate);
+        for &(_, sorter_name, sorter) in &sorters {
+            let avg = bench_sort(
+                &format!("{} on {} input", sorter_name, distribution.name),
+                sorter,
+                &input,
+                args.iterations,
+            );
+            results.push((sorter_name, distribution.name, avg));
+        }
+    }
+    if args.is_selected(SortAlgorithm::SimpleParallel) {
+        let avg = bench_sort(
+            "parallel (simple but with big span) on uniform u64 input",
+            simple_parallel_quicksort,
+            &BenchInput::generate(args.size, |random, len| random.next_vec_of::<u64>(len)),
+            args.iterations,
+        );
+        results.push(("parallel (simple but with big span), u64", "uniform", avg));
+    }
+
+    // В пуле из одного потока параллельная сортировка не должна уступать последовательной
+    #[cfg(feature = "handwritten")]
+    if args.is_selected(SortAlgorithm::PolylogParMemcpy) {
+        let single_thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let single_thread_sorters: [(&str, Sorter); 2] = [
+            ("sequential, 1 thread", sequential_quicksort),
+            (
+                "parallel (with truly polylog span and handmade primitives, parallel memcpy), 1 thread",
+                parallel_quicksort_par_memcpy,
+            ),
+        ];
+        let input = BenchInput::generate(args.size, Random::next_vec);
+        for (sorter_name, sorter) in single_thread_sorters {
+            let avg = single_thread_pool.install(|| {
+                bench_sort(
+                    &format!("{} on uniform input", sorter_name),
+                    sorter,
+                    &input,
+                    args.iterations,
+                )
+            });
+            results.push((sorter_name, "uniform", avg));
+        }
+    }
+
+    println!("Summary");
+    for (sorter_name, distribution_name, avg) in results {
+        println!(
+            "{} | {} | {} ms",
+            sorter_name,
+            distribution_name,
+            avg.as_millis()
+        );
+    }
+}
+
+type Sorter = fn(&mut [i32]);
+
+const USAGE: &str = "usage: cw1-quicksort-rust [--size N] [--iterations N] [--threads N] \
+                     [--algo NAME[,NAME...]]";
+
+/// Параметры бенчмарка, задаваемые аргументами командной строки
+#[derive(Debug, PartialEq)]
+struct BenchArgs {
+    /// Размер массива для равномерного распределения (`--size`)
+    size: usize,
+    /// Количество запусков каждой сортировки (`--iterations`)
+    iterations: u32,
+    /// Количество потоков в пуле rayon (`--threads`)
+    threads: usize,
+    /// Сортировки, которые нужно запустить (`--algo`), `None` - все
+    algorithms: Option<Vec<SortAlgorithm>>,
+}
+
+impl Default for BenchArgs {
+    fn default() -> Self {
+        BenchArgs {
+            size: UNIFORM_LEN,
+            iterations: BENCH_ITERATIONS,
+            threads: BENCH_THREADS,
+            algorithms: None,
+        }
+    }
+}
+
+impl BenchArgs {
+    fn is_selected(&self, algorithm: SortAlgorithm) -> bool {
+        match &self.algorithms {
+            Some(algorithms) => algorithms.contains(&algorithm),
+            None => true,
+        }
+    }
+}
+
+/// Разобрать аргументы вида `--name value`. `--algo` принимает имена через запятую
+/// и может повторяться.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<BenchArgs, String> {
+    let mut res = BenchArgs::default();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", arg))?;
+        let invalid = |_| format!("invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--size" => res.size = value.parse().map_err(invalid)?,
+            "--iterations" => res.iterations = value.parse().map_err(invalid)?,
+            "--threads" => res.threads = value.parse().map_err(invalid)?,
+            "--algo" => {
+                let algorithms = res.algorithms.get_or_insert_with(Vec::new);
+                for name in value.split(',') {
+                    algorithms.push(name.parse().map_err(|error| format!("{}", error))?);
+                }
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    if res.iterations == 0 || res.threads == 0 {
+        return Err("--iterations and --threads must be positive".to_string());
+    }
+    Ok(res)
 }
 
 const BENCH_ITERATIONS: u32 = 5;
 
-fn bench_sort(name: &str, mut sorter: impl FnMut(&mut [i32])) {
+const BENCH_THREADS: usize = 4;
+
+const UNIFORM_LEN: usize = 100_000_000;
+/// Распределение входных данных для бенчмарка
+struct Distribution {
+    name: &'static str,
+    generate: fn(&mut Random, usize) -> Vec<i32>,
+}
+
+const DISTRIBUTIONS: &[Distribution] = &[
+    Distribution {
+        name: "uniform",
+        generate: Random::next_vec,
+    },
+    Distribution {
+        name: "sorted",
+        generate: |random, len| {
+            let mut arr = random.next_vec(len);
+            arr.sort();
+            arr
+        },
+    },
+    Distribution {
+        name: "reversed",
+        generate: |random, len| {
+            let mut arr = random.next_vec(len);
+            arr.sort_by(|a, b| b.cmp(a));
+            arr
+        },
+    },
+    Distribution {
+        name: "all-equal",
+        generate: |random, len| vec![random.next() as i32; len],
+    },
+    Distribution {
+        name: "90%-sorted",
+        generate: |random, len| {
+            let mut arr = random.next_vec(len);
+            arr.sort();
+            // Каждая перестановка портит не более двух элементов
+            for _ in 0..len / 20 {
+                let i = random.next() as usize % len;
+                let j = random.next() as usize % len;
+                arr.swap(i, j);
+            }
+            arr
+        },
+    },
+];
+
+/// Входные данные бенчмарка вместе с ожидаемым результатом сортировки
+struct BenchInput<T> {
+    arr: Vec<T>,
+    expected_arr: Vec<T>,
+}
+
+impl<T: Ord + Clone + Send> BenchInput<T> {
+    /// Генерирует данные с фиксированным сидом, чтобы запуски были воспроизводимы
+    fn generate(len: usize, generate: impl Fn(&mut Random, usize) -> Vec<T>) -> Self {
+        let arr = generate(&mut Random::new(3), len);
+        let mut expected_arr = arr.clone();
+        expected_arr.par_sort();
+        BenchInput { arr, expected_arr }
+    }
+}
+
+#[cfg(feature = "peak-memory")]
+#[global_allocator]
+static ALLOCATOR: PeakAlloc = PeakAlloc::new();
+
+fn bench_sort<T: Ord + Clone + Send + Sync>(
+    name: &str,
+    mut sorter: impl FnMut(&mut [T]),
+    input: &BenchInput<T>,
+    iterations: u32,
+) -> Duration {
     println!("Benchmarking {}", name);
 
-    let mut random = Random::new(3);
-    let total: Duration = (1..=BENCH_ITERATIONS)
+    let total: Duration = (1..=iterations)
         .map(|iteration_num| {
-            let mut arr = random.next_vec(100_000_000);
-            let mut expected_arr = arr.clone();
-            expected_arr.par_sort();
+            let mut arr = input.arr.clone();
+
+            #[cfg(feature = "peak-memory")]
+            let base_memory = {
+                ALLOCATOR.reset_peak();
+                ALLOCATOR.current()
+            };
 
             let start_time = Instant::now();
             sorter(&mut arr);
             let elapsed = start_time.elapsed();
 
+            #[cfg(feature = "peak-memory")]
+            println!(
+                "Iteration {}: {} ms, peak extra memory: {} MB",
+                iteration_num,
+                elapsed.as_millis(),
+                (ALLOCATOR.peak() - base_memory) / (1 << 20)
+            );
+            #[cfg(not(feature = "peak-memory"))]
             println!("Iteration {}: {} ms", iteration_num, elapsed.as_millis());
 
-            assert_eq!(expected_arr, arr);
+            #[cfg(feature = "handwritten")]
+            assert!(par_eq(&input.expected_arr, &arr));
+            #[cfg(not(feature = "handwritten"))]
+            assert!(input.expected_arr == arr);
 
             elapsed
         })
         .sum();
-    let avg = total / BENCH_ITERATIONS;
+    let avg = total / iterations;
 
     println!("Avg time: {} ms", avg.as_millis());
-    println!()
+    println!();
+
+    avg
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<BenchArgs, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_test() {
+        assert_eq!(Ok(BenchArgs::default()), parse(&[]));
+        assert_eq!(
+            Ok(BenchArgs {
+                size: 1000,
+                iterations: 2,
+                threads: 8,
+                algorithms: Some(vec![
+                    SortAlgorithm::Rayon,
+                    SortAlgorithm::Sequential,
+                    SortAlgorithm::SimpleParallel,
+                ]),
+            }),
+            parse(&[
+                "--size",
+                "1000",
+                "--algo",
+                "rayon,sequential",
+                "--iterations",
+                "2",
+                "--threads",
+                "8",
+                "--algo",
+                "simple-parallel",
+            ])
+        );
+
+        assert!(parse(&["--size"]).is_err());
+        assert!(parse(&["--size", "-5"]).is_err());
+        assert!(parse(&["--threads", "0"]).is_err());
+        assert!(parse(&["--algo", "bogo"]).is_err());
+        assert!(parse(&["--verbose", "1"]).is_err());
+    }
 }
@@ -1,5 +1,59 @@
-use crate::utils::UnsafeSlice;
-use num::Num;
+use crate::utils::{Random, UnsafeSlice};
+use alloc::{vec, vec::Vec};
+use core::cmp;
+use core::mem::MaybeUninit;
+use core::ops::Add;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use num::{Num, ToPrimitive};
+
+///////////////////
+// Threads
+///////////////////
+
+/// Выполняется ли код в пуле rayon из одного потока.
+/// В этом случае fork-join только добавляет накладные расходы,
+/// и стоит сразу выбирать последовательный вариант алгоритма.
+/// Без feature `std` потоков нет, и ответ всегда положительный.
+pub fn is_single_threaded() -> bool {
+    current_num_threads() == 1
+}
+
+/// Количество потоков в текущем пуле rayon.
+#[cfg(feature = "std")]
+pub fn current_num_threads() -> usize {
+    rayon::current_num_threads()
+}
+
+/// Без feature `std` потоков нет: fork-join выполняется последовательно (см. [join]).
+#[cfg(not(feature = "std"))]
+pub fn current_num_threads() -> usize {
+    1
+}
+
+/// Fork-join: выполнить `a` и `b`, возможно параллельно, и вернуть оба результата.
+#[cfg(feature = "std")]
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    rayon::join(a, b)
+}
+
+/// Без feature `std` (и rayon) `a` и `b` выполняются последовательно,
+/// поэтому примитивы остаются корректными и под `no_std`.
+#[cfg(not(feature = "std"))]
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    (a(), b())
+}
 
 ///////////////////
 // Parallel for
@@ -7,12 +61,29 @@ use num::Num;
 
 /// Параллельно обработать каждый элемент слайса переданной функцией. O(log n) span
 pub fn par_for<T: Send>(arr: &mut [T], action: impl Fn(usize, &mut T) + Copy + Sync) {
-    par_for_helper(arr, 0, action);
+    const SEQUENTIAL_BLOCK: usize = 4096;
+    par_for_blocked(arr, SEQUENTIAL_BLOCK, action);
 }
 
-fn par_for_helper<T: Send>(arr: &mut [T], l: usize, action: impl Fn(usize, &mut T) + Copy + Sync) {
-    const SEQUENTIAL_BLOCK: usize = 4096;
-    if arr.len() <= SEQUENTIAL_BLOCK {
+/// То же, что и [par_for], но размер блока, обрабатываемого последовательно, задается явно.
+/// Маленький блок улучшает балансировку для тяжелых действий,
+/// большой — уменьшает накладные расходы для легких.
+pub fn par_for_blocked<T: Send>(
+    arr: &mut [T],
+    block: usize,
+    action: impl Fn(usize, &mut T) + Copy + Sync,
+) {
+    assert!(block > 0);
+    par_for_helper(arr, 0, block, action);
+}
+
+fn par_for_helper<T: Send>(
+    arr: &mut [T],
+    l: usize,
+    block: usize,
+    action: impl Fn(usize, &mut T) + Copy + Sync,
+) {
+    if arr.len() <= block {
         arr.iter_mut()
             .enumerate()
             .for_each(|(i, el)| action(i + l, el));
@@ -21,17 +92,82 @@ fn par_for_helper<T: Send>(arr: &mut [T], l: usize, action: impl Fn(usize, &mut
     let m = arr.len() / 2;
     let (left, right) = arr.split_at_mut(m);
     let right_left = l + left.len();
-    rayon::join(
-        || par_for_helper(left, l, action),
-        || par_for_helper(right, right_left, action),
+    join(
+        || par_for_helper(left, l, block, action),
+        || par_for_helper(right, right_left, block, action),
+    );
+}
+
+/// То же, что и [par_for], но верхние уровни рекурсии делят слайс не пополам,
+/// а на `current_num_threads()` непрерывных кусков почти равной длины,
+/// внутри которых дальше работает обычный [par_for].
+/// При равномерной загрузке каждый поток обрабатывает свой непрерывный участок памяти,
+/// что помогает операциям, упирающимся в пропускную способность памяти на NUMA-машинах.
+/// Какой поток возьмет какой кусок, по-прежнему решает планировщик rayon.
+pub fn par_for_numa<T: Send>(arr: &mut [T], action: impl Fn(usize, &mut T) + Copy + Sync) {
+    const SEQUENTIAL_BLOCK: usize = 4096;
+    let chunk = cmp::max(1, arr.len().div_ceil(current_num_threads()));
+    blocked_for_dyn(arr, chunk, |chunk_num, chunk_arr| {
+        par_for_helper(chunk_arr, chunk_num * chunk, SEQUENTIAL_BLOCK, action);
+    });
+}
+
+/// Параллельно заполнить слайс значениями `f(i)`. В отличие от [par_for],
+/// текущие значения элементов не читаются, а перезаписываются. O(log n) span
+pub fn par_fill_with<T: Send>(arr: &mut [T], f: impl Fn(usize) -> T + Copy + Sync) {
+    const SEQUENTIAL_BLOCK: usize = 4096;
+    par_for_helper(arr, 0, SEQUENTIAL_BLOCK, |i, el| *el = f(i));
+}
+
+/// Параллельно применить `transform` на месте к элементам, удовлетворяющим условию,
+/// например ограничить сверху все значения больше порога. O(log n) span
+pub fn par_map_if<T: Send>(
+    arr: &mut [T],
+    pred: impl Fn(&T) -> bool + Copy + Sync,
+    transform: impl Fn(&mut T) + Copy + Sync,
+) {
+    par_for(arr, |_, x| {
+        if pred(x) {
+            transform(x);
+        }
+    });
+}
+
+/// Параллельно построить вектор `[f(0), f(1), ..., f(n - 1)]`
+/// (выделяющий память аналог [par_fill_with]). O(log n) span
+pub fn par_tabulate<R: Send + Default + Clone>(
+    n: usize,
+    f: impl Fn(usize) -> R + Copy + Sync,
+) -> Vec<R> {
+    let mut res: Vec<R> = vec![Default::default(); n];
+    par_fill_with(&mut res, f);
+    res
+}
+
+/// Параллельно вызвать функцию для каждого индекса из [l, r). O(log n) span
+pub fn par_for_range(l: usize, r: usize, action: impl Fn(usize) + Copy + Sync) {
+    const SEQUENTIAL_BLOCK: usize = 4096;
+    if r - l <= SEQUENTIAL_BLOCK {
+        (l..r).for_each(action);
+        return;
+    }
+    let m = l + (r - l) / 2;
+    join(
+        || par_for_range(l, m, action),
+        || par_for_range(m, r, action),
     );
 }
 
+/// Параллельно обработать слайс блоками по `BLOCK_SIZE` элементов:
+/// `action` получает номер блока и сам блок. Для пустого слайса `action` не вызывается.
 pub fn blocked_for<T: Send, const BLOCK_SIZE: usize>(
     arr: &mut [T],
     action: impl Fn(usize, &mut [T]) + Copy + Sync,
 ) {
-    blocked_for_helper::<T, BLOCK_SIZE>(arr, 0, arr.len().div_ceil(SCAN_BLOCK_SIZE), action);
+    if arr.is_empty() {
+        return;
+    }
+    blocked_for_helper::<T, BLOCK_SIZE>(arr, 0, arr.len().div_ceil(BLOCK_SIZE), action);
 }
 
 fn blocked_for_helper<T: Send, const BLOCK_SIZE: usize>(
@@ -44,15 +180,82 @@ fn blocked_for_helper<T: Send, const BLOCK_SIZE: usize>(
         action(block_left, arr);
         return;
     }
-    let m = (block_left + block_right) / 2;
-    let split_point = (m - block_left) * BLOCK_SIZE;
+    let m = block_left + (block_right - block_left) / 2;
+    // m < block_right, поэтому split_point < arr.len() и переполнения быть не должно
+    let split_point = (m - block_left).checked_mul(BLOCK_SIZE);
+    debug_assert!(split_point.is_some_and(|split_point| split_point < arr.len()));
+    let split_point = split_point.unwrap_or(arr.len());
     let (arr_left, arr_right) = arr.split_at_mut(split_point);
-    rayon::join(
+    join(
         || blocked_for_helper::<T, BLOCK_SIZE>(arr_left, block_left, m, action),
         || blocked_for_helper::<T, BLOCK_SIZE>(arr_right, m, block_right, action),
     );
 }
 
+/// То же, что и [blocked_for], но размер блока задается во время выполнения.
+/// Позволяет подобрать размер блока, например, под размер кэша.
+pub fn blocked_for_dyn<T: Send>(
+    arr: &mut [T],
+    block_size: usize,
+    action: impl Fn(usize, &mut [T]) + Copy + Sync,
+) {
+    assert!(block_size > 0);
+    if arr.is_empty() {
+        return;
+    }
+    blocked_for_dyn_helper(arr, block_size, 0, arr.len().div_ceil(block_size), action);
+}
+
+/// Параллельно обработать слайс кусками по `chunk_size` элементов
+/// (аналог `par_chunks_mut(chunk_size).enumerate()`): `action` получает номер куска и сам кусок.
+/// Все куски, кроме, возможно, последнего, имеют длину ровно `chunk_size`.
+/// То же самое, что и [blocked_for_dyn].
+pub fn par_for_chunks<T: Send>(
+    arr: &mut [T],
+    chunk_size: usize,
+    action: impl Fn(usize, &mut [T]) + Copy + Sync,
+) {
+    blocked_for_dyn(arr, chunk_size, action);
+}
+
+fn blocked_for_dyn_helper<T: Send>(
+    arr: &mut [T],
+    block_size: usize,
+    block_left: usize,
+    block_right: usize,
+    action: impl Fn(usize, &mut [T]) + Copy + Sync,
+) {
+    if arr.len() <= block_size {
+        action(block_left, arr);
+        return;
+    }
+    let m = block_left + (block_right - block_left) / 2;
+    // m < block_right, поэтому split_point < arr.len() и переполнения быть не должно
+    let split_point = (m - block_left).checked_mul(block_size);
+    debug_assert!(split_point.is_some_and(|split_point| split_point < arr.len()));
+    let split_point = split_point.unwrap_or(arr.len());
+    let (arr_left, arr_right) = arr.split_at_mut(split_point);
+    join(
+        || blocked_for_dyn_helper(arr_left, block_size, block_left, m, action),
+        || blocked_for_dyn_helper(arr_right, block_size, m, block_right, action),
+    );
+}
+
+///////////////////
+// Copy
+///////////////////
+
+/// Параллельно скопировать `src` в `dst` той же длины: memcpy блоков, запущенный через [blocked_for].
+/// O(log n) span
+pub fn par_copy<T: Copy + Send + Sync>(dst: &mut [T], src: &[T]) {
+    assert_eq!(dst.len(), src.len());
+    const COPY_BLOCK: usize = 4096;
+    blocked_for::<_, COPY_BLOCK>(dst, |block_index, dst_block| {
+        let from = COPY_BLOCK * block_index;
+        dst_block.copy_from_slice(&src[from..from + dst_block.len()]);
+    });
+}
+
 ///////////////////
 // Map
 ///////////////////
@@ -67,7 +270,36 @@ pub fn par_map<T: Send + Sync, R: Send + Default + Clone>(
     res
 }
 
-fn par_map_helper<T: Send + Sync, R: Send>(
+/// Аналог [par_map], не требующий от результата `Default + Clone`
+/// и не тратящий время на предварительное заполнение вектора.
+pub fn par_map_uninit<T: Sync, R: Send>(
+    arr: &[T],
+    mapper: impl Fn(&T) -> R + Copy + Sync,
+) -> Vec<R> {
+    let mut res: Vec<R> = Vec::with_capacity(arr.len());
+    par_map_helper(arr, &mut res.spare_capacity_mut()[..arr.len()], |x| {
+        MaybeUninit::new(mapper(x))
+    });
+    // SAFETY: par_map_helper записывает каждую позицию результата ровно один раз,
+    // так что все arr.len() элементов проинициализированы
+    unsafe {
+        res.set_len(arr.len());
+    }
+    res
+}
+
+/// Параллельно преобразовать слайс, записав результат в переданный слайс той же длины.
+/// В отличие от [par_map] не выделяет память. O(log n) span
+pub fn par_map_into<T: Sync, R: Send>(
+    src: &[T],
+    dst: &mut [R],
+    mapper: impl Fn(&T) -> R + Copy + Sync,
+) {
+    assert_eq!(src.len(), dst.len());
+    par_map_helper(src, dst, mapper);
+}
+
+fn par_map_helper<T: Sync, R: Send>(
     src_arr: &[T],
     result_arr: &mut [R],
     mapper: impl Fn(&T) -> R + Copy + Sync,
@@ -82,12 +314,166 @@ fn par_map_helper<T: Send + Sync, R: Send>(
     let m = src_arr.len() / 2;
     let (src_left, src_right) = src_arr.split_at(m);
     let (result_left, result_right) = result_arr.split_at_mut(m);
-    rayon::join(
+    join(
         || par_map_helper(src_left, result_left, mapper),
         || par_map_helper(src_right, result_right, mapper),
     );
 }
 
+/// Параллельно поэлементно объединить два слайса одинаковой длины,
+/// используя переданную функцию. O(log n) span
+pub fn par_zip_with<A: Sync, B: Sync, R: Send + Default + Clone>(
+    a: &[A],
+    b: &[B],
+    f: impl Fn(&A, &B) -> R + Copy + Sync,
+) -> Vec<R> {
+    assert_eq!(a.len(), b.len());
+    let mut res: Vec<R> = vec![Default::default(); a.len()];
+    par_zip_with_helper(a, b, &mut res, f);
+    res
+}
+
+fn par_zip_with_helper<A: Sync, B: Sync, R: Send>(
+    a: &[A],
+    b: &[B],
+    result_arr: &mut [R],
+    f: impl Fn(&A, &B) -> R + Copy + Sync,
+) {
+    if a.len() <= 4096 {
+        result_arr
+            .iter_mut()
+            .zip(a.iter().zip(b.iter()))
+            .for_each(|(res, (x, y))| *res = f(x, y));
+        return;
+    }
+    let m = a.len() / 2;
+    let (a_left, a_right) = a.split_at(m);
+    let (b_left, b_right) = b.split_at(m);
+    let (result_left, result_right) = result_arr.split_at_mut(m);
+    join(
+        || par_zip_with_helper(a_left, b_left, result_left, f),
+        || par_zip_with_helper(a_right, b_right, result_right, f),
+    );
+}
+
+///////////////////
+// Gather / Scatter
+///////////////////
+
+/// Параллельно собрать элементы по индексам: `out[i] = src[indices[i]]`
+/// (например, применить перестановку или выбрать подмножество). O(log n) span
+pub fn par_gather<T: Copy + Send + Sync>(src: &[T], indices: &[usize]) -> Vec<T> {
+    par_map_uninit(indices, |&i| src[i])
+}
+
+/// Параллельно разложить элементы по индексам: `dst[indices[i]] = src[i]`.
+/// Выход индекса за границы `dst` всегда приводит к панике, а в отладочной сборке
+/// еще и проверяется заранее вместе с уникальностью индексов.
+/// O(log n) span
+///
+/// # Safety
+///
+/// Индексы должны быть попарно различны, иначе в `dst` будет гонка данных.
+pub unsafe fn par_scatter<T: Copy + Send + Sync>(src: &[T], indices: &[usize], dst: &mut [T]) {
+    assert_eq!(src.len(), indices.len());
+    debug_assert!(
+        par_all(indices, |&i| i < dst.len()),
+        "scatter index out of bounds"
+    );
+    #[cfg(debug_assertions)]
+    {
+        let seen: Vec<AtomicBool> = (0..dst.len()).map(|_| AtomicBool::new(false)).collect();
+        debug_assert!(
+            !par_any(indices, |&i| seen[i].swap(true, Ordering::Relaxed)),
+            "scatter indices are not unique"
+        );
+    }
+    let dst = UnsafeSlice::new(dst);
+    par_for_range(0, src.len(), |i| unsafe { dst.write(indices[i], src[i]) });
+}
+
+/// Параллельно переупорядочить массив перестановкой: `out[i] = arr[perm[i]]`
+/// (например, перестановкой из `parallel_argsort`). Реализовано через [par_gather].
+///
+/// Версия на месте заметно сложнее: перестановка распадается на циклы,
+/// элементы каждого цикла приходится сдвигать последовательно, а длина цикла может быть O(n),
+/// так что для хорошего span нужен дополнительный буфер, как здесь.
+pub fn par_apply_permutation<T: Copy + Send + Sync>(arr: &[T], perm: &[usize]) -> Vec<T> {
+    assert_eq!(arr.len(), perm.len());
+    par_gather(arr, perm)
+}
+
+///////////////////
+// Reduce
+///////////////////
+
+/// Параллельно вычислить `combine(map(x_0), combine(map(x_1), ...))`, не сохраняя
+/// промежуточный массив результатов `map`. `combine` должна быть ассоциативной,
+/// а `identity` - ее нейтральным элементом. O(log n) span
+pub fn par_map_reduce<T: Sync, R: Send + Copy>(
+    arr: &[T],
+    map: impl Fn(&T) -> R + Copy + Sync,
+    identity: R,
+    combine: impl Fn(R, R) -> R + Copy + Sync,
+) -> R {
+    if arr.len() <= 4096 {
+        return arr.iter().fold(identity, |acc, x| combine(acc, map(x)));
+    }
+    let (left, right) = arr.split_at(arr.len() / 2);
+    // identity перемещается в задачи копией, так что от R не требуется Sync
+    let (map, combine) = (&map, &combine);
+    let (left_res, right_res) = join(
+        move || par_map_reduce(left, *map, identity, *combine),
+        move || par_map_reduce(right, *map, identity, *combine),
+    );
+    combine(left_res, right_res)
+}
+
+/// Параллельно просуммировать `f64` попарным (каскадным) суммированием:
+/// слайс делится пополам вплоть до блоков из нескольких элементов, как в дереве fork-join.
+/// Погрешность растет как O(ε log n) от суммы модулей, а не O(ε n),
+/// как при последовательном суммировании слева направо. O(log n) span
+pub fn par_sum_f64(arr: &[f64]) -> f64 {
+    if arr.len() <= 4096 {
+        return pairwise_sum(arr);
+    }
+    let (left, right) = arr.split_at(arr.len() / 2);
+    let (left_sum, right_sum) = join(|| par_sum_f64(left), || par_sum_f64(right));
+    left_sum + right_sum
+}
+
+fn pairwise_sum(arr: &[f64]) -> f64 {
+    if arr.len() <= 8 {
+        return arr.iter().sum();
+    }
+    let (left, right) = arr.split_at(arr.len() / 2);
+    pairwise_sum(left) + pairwise_sum(right)
+}
+
+/// Параллельно свернуть каждый кусок из `chunk` элементов операцией `op`
+/// (последний кусок может быть неполным) и вернуть по одному значению на кусок.
+/// Результаты раздаются блоками через [blocked_for_dyn] так,
+/// чтобы на задачу приходилось порядка [SCAN_BLOCK_SIZE] элементов исходного слайса.
+pub fn par_chunk_reduce<T: Send + Sync + Copy>(
+    arr: &[T],
+    chunk: usize,
+    identity: T,
+    op: impl Fn(T, T) -> T + Copy + Sync,
+) -> Vec<T> {
+    assert!(chunk > 0);
+    let mut res = vec![identity; arr.len().div_ceil(chunk)];
+    let chunks_per_block = cmp::max(1, SCAN_BLOCK_SIZE / chunk);
+    blocked_for_dyn(&mut res, chunks_per_block, |block_num, block| {
+        let first_chunk = block_num * chunks_per_block;
+        for (i, x) in block.iter_mut().enumerate() {
+            let from = (first_chunk + i) * chunk;
+            let to = cmp::min(from + chunk, arr.len());
+            *x = arr[from..to].iter().fold(identity, |acc, &y| op(acc, y));
+        }
+    });
+    res
+}
+
 ///////////////////
 // Scan
 ///////////////////
@@ -95,31 +481,43 @@ fn par_map_helper<T: Send + Sync, R: Send>(
 const SCAN_BLOCK_SIZE: usize = 1024 * 4;
 
 /// Параллельно вычислить невключительные префиксные суммы.
-/// Написанная реализация имеет O(log^2 n) span.
-/// В то же время можно раскомментировать вызов [par_inline_prefix_sums_helper] (убрав рекурсию),
-/// тогда будет O(log n) span.
-/// Разницы по времени практически нет, зато рекурсивное сведение использует
-/// меньше дополнительной памяти и проще для восприятия.
-pub fn par_inline_prefix_sums<T: Num + Copy + Send + Sync>(arr: &mut [T]) {
+/// Возвращает сумму всех чисел.
+/// По умолчанию реализация имеет O(log^2 n) span.
+/// При включенной feature `log-span-scan` суммы блоков считаются через
+/// [par_inline_prefix_sums_helper] (без рекурсии), тогда будет O(log n) span.
+/// Разницы по времени практически нет, зато рекурсивное сведение
+/// проще для восприятия.
+pub fn par_inline_prefix_sums<T: Num + Copy + Send + Sync>(arr: &mut [T]) -> T {
+    par_inline_prefix_sums_with_zero(arr, T::zero())
+}
+
+/// То же, что и [par_inline_prefix_sums], но вместо `num::Num` требуется только сложение,
+/// а нейтральный элемент `zero` передается явно. Подходит, например,
+/// для массивов смещений типа `usize` в обобщенном коде.
+pub fn par_inline_prefix_sums_with_zero<T: Add<Output = T> + Copy + Send + Sync>(
+    arr: &mut [T],
+    zero: T,
+) -> T {
     if arr.len() <= SCAN_BLOCK_SIZE {
-        inline_pref_sums(arr);
-        return;
+        return inline_pref_sums(arr, zero);
     }
 
     let block_count = arr.len().div_ceil(SCAN_BLOCK_SIZE);
-    let mut block_sums: Vec<T> = vec![T::zero(); block_count];
+    let mut block_sums: Vec<T> = vec![zero; block_count];
 
     // Считаем суммы внутри блоков
     let block_sums_unsafe_slice = UnsafeSlice::new(&mut block_sums);
     blocked_for::<_, SCAN_BLOCK_SIZE>(arr, |block_num, block| unsafe {
-        block_sums_unsafe_slice.write(block_num, inline_pref_sums(block));
+        block_sums_unsafe_slice.write(block_num, inline_pref_sums(block, zero));
     });
 
     // Теперь считаем префиксные суммы по блокам.
     // Можно рекурсивно свестись, что даст O(log^2 n) span.
-    par_inline_prefix_sums(&mut block_sums);
+    #[cfg(not(feature = "log-span-scan"))]
+    let sum = par_inline_prefix_sums_with_zero(&mut block_sums, zero);
     // Но можно посчитать суммы блоков за O(log n).
-    // par_inline_prefix_sums_helper(&mut block_sums);
+    #[cfg(feature = "log-span-scan")]
+    let sum = par_inline_prefix_sums_helper(&mut block_sums, zero);
 
     // Наконец, окончательно вычисляем префиксные суммы,
     // добавляя к суммам внутри блоков префиксные суммы по блокам
@@ -128,12 +526,191 @@ pub fn par_inline_prefix_sums<T: Num + Copy + Send + Sync>(arr: &mut [T]) {
         let prev_sum = block_sums_ref[block_num];
         block.iter_mut().for_each(|el| *el = *el + prev_sum);
     });
+
+    sum
 }
 
-/// Последовательно посчитать невключительные префиксные суммы.
-/// Возвращает сумму всех чисел.
-fn inline_pref_sums<T: Num + Copy>(arr: &mut [T]) -> T {
+/// Параллельно вычислить невключительные префиксные суммы по сегментам:
+/// `segment_flags[i] == true` означает, что с i-го элемента начинается новый сегмент,
+/// и сумма на нем обнуляется. O(log^2 n) span.
+///
+/// Как и в [par_inline_prefix_sums], сначала считаются суммы внутри блоков,
+/// затем рекурсивно - сегментированные суммы по блокам, и наконец к элементам блока
+/// до первого начала сегмента в нем добавляется сумма, пришедшая из предыдущих блоков.
+pub fn par_segmented_prefix_sums<T: Num + Copy + Send + Sync>(
+    arr: &mut [T],
+    segment_flags: &[bool],
+) {
+    assert_eq!(arr.len(), segment_flags.len());
+    if arr.len() <= SCAN_BLOCK_SIZE {
+        inline_segmented_pref_sums(arr, segment_flags);
+        return;
+    }
+
+    let block_count = arr.len().div_ceil(SCAN_BLOCK_SIZE);
+    // Сумма элементов блока после последнего начала сегмента в нем
+    let mut block_sums: Vec<T> = vec![T::zero(); block_count];
+    // Начинается ли в блоке хотя бы один сегмент
+    let mut block_flags: Vec<bool> = vec![false; block_count];
+
+    let block_sums_unsafe_slice = UnsafeSlice::new(&mut block_sums);
+    let block_flags_unsafe_slice = UnsafeSlice::new(&mut block_flags);
+    blocked_for::<_, SCAN_BLOCK_SIZE>(arr, |block_num, block| unsafe {
+        let from = block_num * SCAN_BLOCK_SIZE;
+        let flags = &segment_flags[from..from + block.len()];
+        block_sums_unsafe_slice.write(block_num, inline_segmented_pref_sums(block, flags));
+        block_flags_unsafe_slice.write(block_num, flags.contains(&true));
+    });
+
+    // Сумма, приходящая в блок b + 1, равна включительной сегментированной сумме по блокам
+    // до b, то есть невключительной на b плюс сумме самого блока b.
+    let mut carries = block_sums.clone();
+    par_segmented_prefix_sums(&mut carries, &block_flags);
+    let carries_ref: &[T] = &carries;
+    let block_sums_ref: &[T] = &block_sums;
+
+    blocked_for::<_, SCAN_BLOCK_SIZE>(arr, |block_num, block| {
+        if block_num == 0 {
+            return;
+        }
+        let carry = carries_ref[block_num - 1] + block_sums_ref[block_num - 1];
+        let from = block_num * SCAN_BLOCK_SIZE;
+        let flags = &segment_flags[from..from + block.len()];
+        block
+            .iter_mut()
+            .zip(flags)
+            .take_while(|(_, &flag)| !flag)
+            .for_each(|(el, _)| *el = *el + carry);
+    });
+}
+
+/// Последовательно посчитать невключительные префиксные суммы по сегментам.
+/// Возвращает сумму элементов после начала последнего сегмента.
+fn inline_segmented_pref_sums<T: Num + Copy>(arr: &mut [T], segment_flags: &[bool]) -> T {
     let mut sum = T::zero();
+    for (el, &flag) in arr.iter_mut().zip(segment_flags) {
+        if flag {
+            sum = T::zero();
+        }
+        let el_copy = *el;
+        *el = sum;
+        sum = sum + el_copy;
+    }
+    sum
+}
+
+/// Параллельно вычислить невключительные префиксные суммы, не изменяя исходный массив.
+/// Возвращает вектор префиксных сумм и сумму всех чисел.
+pub fn par_prefix_sums<T: Num + Copy + Send + Sync>(arr: &[T]) -> (Vec<T>, T) {
+    let mut res = par_map_uninit(arr, |&x| x);
+    let sum = par_inline_prefix_sums(&mut res);
+    (res, sum)
+}
+
+/// Параллельно вычислить невключительные префиксные суммы `i32`, накапливая их в `i64`.
+/// Суммы больших массивов (например, масок) могут не поместиться в `i32`,
+/// а расширение при чтении позволяет не менять тип исходного массива.
+pub fn par_prefix_sums_widening(arr: &[i32]) -> Vec<i64> {
+    let mut res = par_map_uninit(arr, |&x| i64::from(x));
+    par_inline_prefix_sums(&mut res);
+    res
+}
+
+/// Параллельно записать в `dst` невключительные префиксные суммы `src` (слайсы одинаковой длины).
+///
+/// В отличие от копирования с последующим [par_inline_prefix_sums], `dst` записывается
+/// только один раз: сначала по `src` считаются суммы блоков, затем каждый блок
+/// последовательно заполняется, начиная с префиксной суммы предыдущих блоков.
+pub fn par_scan_to<T: Num + Copy + Send + Sync>(src: &[T], dst: &mut [T]) {
+    assert_eq!(src.len(), dst.len());
+    if src.len() <= SCAN_BLOCK_SIZE {
+        pref_sums_to(src, dst, T::zero());
+        return;
+    }
+
+    let block_count = src.len().div_ceil(SCAN_BLOCK_SIZE);
+    let mut block_sums: Vec<T> = vec![T::zero(); block_count];
+    par_for_blocked(&mut block_sums, 1, |block_num, sum| {
+        let from = block_num * SCAN_BLOCK_SIZE;
+        let to = cmp::min(from + SCAN_BLOCK_SIZE, src.len());
+        *sum = src[from..to].iter().fold(T::zero(), |acc, &x| acc + x);
+    });
+    par_inline_prefix_sums(&mut block_sums);
+
+    let block_sums_ref: &[T] = &block_sums;
+    blocked_for::<_, SCAN_BLOCK_SIZE>(dst, |block_num, block| {
+        let from = block_num * SCAN_BLOCK_SIZE;
+        pref_sums_to(
+            &src[from..from + block.len()],
+            block,
+            block_sums_ref[block_num],
+        );
+    });
+}
+
+/// Последовательно записать в `dst` невключительные префиксные суммы `src`,
+/// начиная с `init`.
+fn pref_sums_to<T: Num + Copy>(src: &[T], dst: &mut [T], init: T) {
+    let mut sum = init;
+    for (el, &x) in dst.iter_mut().zip(src) {
+        *el = sum;
+        sum = sum + x;
+    }
+}
+
+/// Параллельно вычислить включительный префиксный "скан" по произвольной ассоциативной
+/// операции `op` с нейтральным элементом `identity`, не изменяя исходный массив:
+/// `res[i] = op(arr[0], op(arr[1], ... arr[i]))`. Например, с `cmp::max` - бегущий максимум.
+///
+/// Устроен так же, как [par_inline_prefix_sums]: итоги блоков, рекурсивный скан по ним
+/// и последовательный проход по каждому блоку со своим начальным значением. O(log^2 n) span
+pub fn par_inclusive_scan<T: Send + Sync + Copy>(
+    arr: &[T],
+    identity: T,
+    op: impl Fn(T, T) -> T + Copy + Sync,
+) -> Vec<T> {
+    let mut res = vec![identity; arr.len()];
+    if arr.len() <= SCAN_BLOCK_SIZE {
+        inclusive_scan_to(arr, &mut res, identity, op);
+        return res;
+    }
+
+    let block_count = arr.len().div_ceil(SCAN_BLOCK_SIZE);
+    let mut block_totals = vec![identity; block_count];
+    par_for_blocked(&mut block_totals, 1, |block_num, total| {
+        let from = block_num * SCAN_BLOCK_SIZE;
+        let to = cmp::min(from + SCAN_BLOCK_SIZE, arr.len());
+        *total = arr[from..to].iter().fold(identity, |acc, &x| op(acc, x));
+    });
+    let block_totals = par_inclusive_scan(&block_totals, identity, op);
+
+    let block_totals_ref: &[T] = &block_totals;
+    blocked_for::<_, SCAN_BLOCK_SIZE>(&mut res, |block_num, block| {
+        let from = block_num * SCAN_BLOCK_SIZE;
+        let init = if block_num == 0 {
+            identity
+        } else {
+            block_totals_ref[block_num - 1]
+        };
+        inclusive_scan_to(&arr[from..from + block.len()], block, init, op);
+    });
+
+    res
+}
+
+/// Последовательно записать в `dst` включительный скан `src`, начиная с `init`.
+fn inclusive_scan_to<T: Copy>(src: &[T], dst: &mut [T], init: T, op: impl Fn(T, T) -> T) {
+    let mut acc = init;
+    for (el, &x) in dst.iter_mut().zip(src) {
+        acc = op(acc, x);
+        *el = acc;
+    }
+}
+
+/// Последовательно посчитать невключительные префиксные суммы, начиная с `zero`.
+/// Возвращает сумму всех чисел.
+fn inline_pref_sums<T: Add<Output = T> + Copy>(arr: &mut [T], zero: T) -> T {
+    let mut sum = zero;
     for el in arr.iter_mut() {
         let el_copy = *el;
         *el = sum;
@@ -142,57 +719,63 @@ fn inline_pref_sums<T: Num + Copy>(arr: &mut [T]) -> T {
     sum
 }
 
-/// Вычислить префиксные суммы за O(log n) span.
+/// Вычислить префиксные суммы за O(log n) span. Возвращает сумму всех чисел.
+///
+/// Для спуска по дереву нужны только суммы левых поддеревьев внутренних вершин.
+/// Внутренних вершин n - 1, и у каждой своя точка разбиения m (1 <= m < n),
+/// поэтому сумму левого поддерева храним по индексу m - 1.
+/// Итого n - 1 дополнительных элементов вместо 4n при явной нумерации вершин.
 #[allow(dead_code)]
-fn par_inline_prefix_sums_helper<T: Num + Copy + Send + Sync>(arr: &mut [T]) {
+fn par_inline_prefix_sums_helper<T: Add<Output = T> + Copy + Send + Sync>(
+    arr: &mut [T],
+    zero: T,
+) -> T {
     if arr.len() <= SCAN_BLOCK_SIZE {
-        inline_pref_sums(arr);
-        return;
+        return inline_pref_sums(arr, zero);
     }
-    let mut partial_sums = vec![T::zero(); 4 * arr.len()];
-    let partial_sums_unsafe_slice = UnsafeSlice::new(&mut partial_sums);
-    prefix_sums_up(arr, partial_sums_unsafe_slice, 0);
-    prefix_sums_down(arr, &partial_sums, T::zero(), 0);
+    let mut left_sums = vec![zero; arr.len() - 1];
+    let left_sums_unsafe_slice = UnsafeSlice::new(&mut left_sums);
+    let sum = prefix_sums_up(arr, left_sums_unsafe_slice, 0);
+    prefix_sums_down(arr, &left_sums, zero, 0);
+    sum
 }
 
-fn prefix_sums_up<T: Num + Copy + Send + Sync>(
+fn prefix_sums_up<T: Add<Output = T> + Copy + Send + Sync>(
     arr: &[T],
-    partial_sums: UnsafeSlice<T>,
-    id: usize,
+    left_sums: UnsafeSlice<T>,
+    l: usize,
 ) -> T {
     if arr.len() == 1 {
-        unsafe {
-            partial_sums.write(id, arr[0]);
-        }
         return arr[0];
     }
-    let (left, right) = arr.split_at(arr.len() / 2);
-    let (left_sum, right_sum) = rayon::join(
-        || prefix_sums_up(left, partial_sums, 2 * id + 1),
-        || prefix_sums_up(right, partial_sums, 2 * id + 2),
+    let m = arr.len() / 2;
+    let (left, right) = arr.split_at(m);
+    let (left_sum, right_sum) = join(
+        || prefix_sums_up(left, left_sums, l),
+        || prefix_sums_up(right, left_sums, l + m),
     );
-    let sum = left_sum + right_sum;
     unsafe {
-        partial_sums.write(id, sum);
+        left_sums.write(l + m - 1, left_sum);
     }
-    sum
+    left_sum + right_sum
 }
 
-fn prefix_sums_down<T: Num + Copy + Send + Sync>(
+fn prefix_sums_down<T: Add<Output = T> + Copy + Send + Sync>(
     arr: &mut [T],
-    partial_sums: &[T],
+    left_sums: &[T],
     left_sum: T,
-    id: usize,
+    l: usize,
 ) {
     if arr.len() == 1 {
         arr[0] = left_sum;
         return;
     }
-    let (left, right) = arr.split_at_mut(arr.len() / 2);
-    let right_left_sum = left_sum + partial_sums[2 * id + 1];
-    rayon::join(
-        || prefix_sums_down(left, partial_sums, left_sum, 2 * id + 1),
-        || prefix_sums_down(right, partial_sums, right_left_sum, 2 * id + 2),
+    let m = arr.len() / 2;
+    let (left, right) = arr.split_at_mut(m);
+    let right_left_sum = left_sum + left_sums[l + m - 1];
+    join(
+        || prefix_sums_down(left, left_sums, left_sum, l),
+        || prefix_sums_down(right, left_sums, right_left_sum, l + m),
     );
 }
 
@@ -201,83 +784,1483 @@ fn prefix_sums_down<T: Num + Copy + Send + Sync>(
 ///////////////////
 
 /// Параллельно отфильтровать массив по условию. Возвращает вектор с подходящими элементами.
+/// Условие вычисляется ровно один раз для каждого элемента.
+/// В пуле из одного потока фильтрует последовательно.
 pub fn par_filter<T: Send + Default + Sync + Copy>(
     arr: &[T],
     condition: impl Fn(&T) -> bool + Copy + Sync,
 ) -> Vec<T> {
-    if arr.is_empty() {
-        return vec![];
+    par_filter_with_count(arr, condition).0
+}
+
+/// То же, что и [par_filter], но вместе с вектором возвращает количество подходящих элементов.
+/// Оно берется из общей суммы префиксных сумм по маске, поэтому ничего не стоит
+/// и по построению совпадает с длиной вектора.
+pub fn par_filter_with_count<T: Send + Default + Sync + Copy>(
+    arr: &[T],
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) -> (Vec<T>, usize) {
+    if is_single_threaded() {
+        let res_arr: Vec<T> = arr.iter().filter(|x| condition(x)).copied().collect();
+        let filtered_count = res_arr.len();
+        return (res_arr, filtered_count);
     }
 
-    let mut mask: Vec<i32> = par_map(arr, |x| if condition(x) { 1 } else { 0 });
-    par_inline_prefix_sums(&mut mask);
+    let (mask, filtered_count) = filter_mask(arr, condition);
 
-    let filtered_count =
-        *mask.last().unwrap() as usize + if condition(arr.last().unwrap()) { 1 } else { 0 };
     let mut res_arr = vec![T::default(); filtered_count];
-
     let res_arr_ref = UnsafeSlice::new(&mut res_arr);
-    par_for(&mut mask, |i, res_pos| unsafe {
-        if condition(&arr[i]) {
-            res_arr_ref.write(*res_pos as usize, arr[i]);
-        }
+    filter_scatter(&mask, filtered_count, |i, pos| unsafe {
+        res_arr_ref.write(pos, arr[i]);
     });
 
-    res_arr
+    (res_arr, filtered_count)
 }
 
-///////////////////
-// Tests
-///////////////////
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::utils::Random;
+/// Аналог [par_filter] для типов, которые можно только клонировать.
+///
+/// [par_filter] требует `Default`, чтобы заранее заполнить результат,
+/// и `Copy`, чтобы дешево копировать элементы. Здесь же клоны подходящих элементов
+/// записываются сразу в неинициализированную память вектора.
+pub fn par_filter_cloned<T: Clone + Send + Sync>(
+    arr: &[T],
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) -> Vec<T> {
+    let (mask, filtered_count) = filter_mask(arr, condition);
 
-    #[test]
-    fn par_for_test() {
-        let mut arr = vec![1, 2, 3, 4, 5];
-        par_for(&mut arr, |i, x| *x += 2 * i);
-        assert_eq!(vec![1, 4, 7, 10, 13], arr);
+    let mut res_arr: Vec<T> = Vec::with_capacity(filtered_count);
+    let res_arr_ref = UnsafeSlice::new(&mut res_arr.spare_capacity_mut()[..filtered_count]);
+    filter_scatter(&mask, filtered_count, |i, pos| unsafe {
+        res_arr_ref.write(pos, MaybeUninit::new(arr[i].clone()));
+    });
+    // SAFETY: каждая из первых filtered_count позиций записана ровно один раз
+    unsafe {
+        res_arr.set_len(filtered_count);
     }
 
-    #[test]
-    fn par_map_test() {
+    res_arr
+}
+
+/// То же, что и [par_filter], но подходящие элементы записываются в начало `dst`
+/// без выделения памяти под результат. Возвращает количество записанных элементов.
+/// Паникует, если подходящих элементов больше, чем `dst.len()`.
+pub fn par_copy_if<T: Copy + Send + Sync>(
+    src: &[T],
+    dst: &mut [T],
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) -> usize {
+    let (mask, filtered_count) = filter_mask(src, condition);
+    assert!(
+        filtered_count <= dst.len(),
+        "destination is too small: {} elements match, capacity is {}",
+        filtered_count,
+        dst.len()
+    );
+
+    let dst_ref = UnsafeSlice::new(dst);
+    filter_scatter(&mask, filtered_count, |i, pos| unsafe {
+        dst_ref.write(pos, src[i]);
+    });
+
+    filtered_count
+}
+
+/// Параллельно найти индексы элементов, удовлетворяющих условию, в порядке возрастания.
+/// Работает так же, как [par_filter], но записывает в результат индекс, а не сам элемент.
+pub fn par_positions<T: Sync>(
+    arr: &[T],
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) -> Vec<usize> {
+    let (mask, filtered_count) = filter_mask(arr, condition);
+
+    let mut positions = vec![0; filtered_count];
+    let positions_ref = UnsafeSlice::new(&mut positions);
+    filter_scatter(&mask, filtered_count, |i, pos| unsafe {
+        positions_ref.write(pos, i);
+    });
+
+    positions
+}
+
+/// Параллельно оставить в векторе только элементы, удовлетворяющие условию,
+/// сохраняя их относительный порядок (аналог [Vec::retain]).
+/// Подходящие элементы собираются в буфер размера их количества, а не копии всего вектора,
+/// и затем копируются в начало вектора.
+pub fn par_retain<T: Copy + Send + Sync>(
+    arr: &mut Vec<T>,
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) {
+    let (mask, filtered_count) = filter_mask(arr, condition);
+
+    let mut kept: Vec<T> = Vec::with_capacity(filtered_count);
+    let kept_ref = UnsafeSlice::new(&mut kept.spare_capacity_mut()[..filtered_count]);
+    filter_scatter(&mask, filtered_count, |i, pos| unsafe {
+        kept_ref.write(pos, MaybeUninit::new(arr[i]));
+    });
+    // SAFETY: каждая из первых filtered_count позиций записана ровно один раз
+    unsafe {
+        kept.set_len(filtered_count);
+    }
+
+    par_copy(&mut arr[..filtered_count], &kept);
+    arr.truncate(filtered_count);
+}
+
+/// Параллельно разбить массив на три части за один проход:
+/// элементы, для которых `classify` вернул `Less`, `Equal` и `Greater` соответственно.
+/// Относительный порядок внутри частей сохраняется.
+///
+/// В отличие от трех вызовов [par_filter], исходный массив читается только дважды:
+/// при классификации и при записи результата, а префиксные суммы считаются
+/// только для частей `Less` и `Greater`.
+pub fn par_partition3<T: Send + Sync + Copy>(
+    arr: &[T],
+    classify: impl Fn(&T) -> cmp::Ordering + Copy + Sync,
+) -> (Vec<T>, Vec<T>, Vec<T>) {
+    // Класс элемента: -1, 0 или 1
+    let classes: Vec<i8> = par_map(arr, |x| classify(x) as i8);
+    let (less_mask, less_count) = filter_mask(&classes, |&c| c < 0);
+    let (greater_mask, greater_count) = filter_mask(&classes, |&c| c > 0);
+    // Отдельная маска для равных не строится: перед i-м элементом ровно
+    // i - less_mask[i] - greater_mask[i] равных. Обычно (например, при уникальном
+    // опорном элементе) часть равных крошечная, и третий проход с префиксными суммами
+    // был бы почти бесполезен.
+    let eq_count = arr.len() - less_count - greater_count;
+
+    let mut less: Vec<T> = Vec::with_capacity(less_count);
+    let mut eq: Vec<T> = Vec::with_capacity(eq_count);
+    let mut greater: Vec<T> = Vec::with_capacity(greater_count);
+
+    let less_ref = UnsafeSlice::new(&mut less.spare_capacity_mut()[..less_count]);
+    let eq_ref = UnsafeSlice::new(&mut eq.spare_capacity_mut()[..eq_count]);
+    let greater_ref = UnsafeSlice::new(&mut greater.spare_capacity_mut()[..greater_count]);
+    par_for_range(0, arr.len(), |i| unsafe {
+        let x = MaybeUninit::new(arr[i]);
+        match classes[i].cmp(&0) {
+            cmp::Ordering::Less => less_ref.write(less_mask[i] as usize, x),
+            cmp::Ordering::Equal => {
+                eq_ref.write(i - less_mask[i] as usize - greater_mask[i] as usize, x)
+            }
+            cmp::Ordering::Greater => greater_ref.write(greater_mask[i] as usize, x),
+        }
+    });
+    // SAFETY: каждый элемент попадает ровно в одну часть, и позиции внутри части
+    // различны, так что все позиции каждой части записаны ровно один раз
+    unsafe {
+        less.set_len(less_count);
+        eq.set_len(eq_count);
+        greater.set_len(greater_count);
+    }
+
+    (less, eq, greater)
+}
+
+/// Параллельно устойчиво разбить массив: вернуть вектор, в начале которого стоят
+/// элементы, удовлетворяющие условию, а за ними - остальные, причем в обеих частях
+/// сохраняется исходный относительный порядок. Вторым значением возвращается
+/// количество подходящих элементов (индекс границы).
+///
+/// Позиция подходящего элемента - префиксная сумма по маске, а неподходящего -
+/// количество неподходящих перед ним, то есть `i - mask[i]`, плюс граница.
+pub fn par_stable_partition<T: Copy + Send + Sync>(
+    arr: &[T],
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) -> (Vec<T>, usize) {
+    let (mask, split) = filter_mask(arr, condition);
+
+    let mut res: Vec<T> = Vec::with_capacity(arr.len());
+    let res_ref = UnsafeSlice::new(&mut res.spare_capacity_mut()[..arr.len()]);
+    par_for_range(0, arr.len(), |i| {
+        let is_matching = mask.get(i + 1).map_or(split, |&pos| pos as usize) != mask[i] as usize;
+        let pos = if is_matching {
+            mask[i] as usize
+        } else {
+            split + i - mask[i] as usize
+        };
+        unsafe {
+            res_ref.write(pos, MaybeUninit::new(arr[i]));
+        }
+    });
+    // SAFETY: позиции подходящих элементов занимают [0, split),
+    // неподходящих - [split, arr.len()), и все они различны
+    unsafe {
+        res.set_len(arr.len());
+    }
+
+    (res, split)
+}
+
+/// Параллельно переставить элементы слайса так, чтобы все элементы меньше `pivot`
+/// оказались в начале. Возвращает количество таких элементов (границу разбиения).
+/// Порядок внутри частей не сохраняется.
+///
+/// В отличие от [par_partition3], дополнительная память нужна только под счетчики блоков:
+/// сначала каждый блок разбивается на месте последовательно, затем по префиксным суммам
+/// находятся элементы, оказавшиеся не по свою сторону от границы. Их поровну,
+/// и i-й такой элемент слева параллельно меняется местами с i-м справа.
+pub fn par_partition_in_place<T: Ord + Copy + Send + Sync>(arr: &mut [T], pivot: &T) -> usize {
+    const PARTITION_BLOCK_SIZE: usize = 4096;
+    if arr.len() <= PARTITION_BLOCK_SIZE {
+        return partition_less(arr, pivot);
+    }
+
+    let len = arr.len();
+    let block_count = len.div_ceil(PARTITION_BLOCK_SIZE);
+    let mut less_counts = vec![0; block_count];
+    let less_counts_ref = UnsafeSlice::new(&mut less_counts);
+    blocked_for::<_, PARTITION_BLOCK_SIZE>(arr, |block_num, block| unsafe {
+        less_counts_ref.write(block_num, partition_less(block, pivot));
+    });
+    let boundary = par_map_reduce(&less_counts, |&count| count, 0, |a, b| a + b);
+
+    // Каждый блок имеет вид [меньшие, не меньшие]. Лишние не меньшие - те, что левее границы,
+    // лишние меньшие - те, что правее. Для каждого блока храним, с какой позиции они начинаются,
+    // и невключительные префиксные суммы их количеств.
+    let block_start = |block_num: usize| block_num * PARTITION_BLOCK_SIZE;
+    let block_end = |block_num: usize| cmp::min(block_start(block_num + 1), len);
+    let misplaced_ge_from = |block_num: usize| block_start(block_num) + less_counts[block_num];
+    let misplaced_less_from = |block_num: usize| cmp::max(block_start(block_num), boundary);
+
+    let mut misplaced_ge = vec![0; block_count];
+    par_fill_with(&mut misplaced_ge, |block_num| {
+        cmp::min(block_end(block_num), boundary).saturating_sub(misplaced_ge_from(block_num))
+    });
+    let mut misplaced_less = vec![0; block_count];
+    par_fill_with(&mut misplaced_less, |block_num| {
+        misplaced_ge_from(block_num).saturating_sub(misplaced_less_from(block_num))
+    });
+    let misplaced_count = par_inline_prefix_sums(&mut misplaced_ge);
+    par_inline_prefix_sums(&mut misplaced_less);
+
+    let arr_ref = UnsafeSlice::new(arr);
+    par_for_range(0, misplaced_count, |i| {
+        let ge_block = misplaced_ge.partition_point(|&offset| offset <= i) - 1;
+        let less_block = misplaced_less.partition_point(|&offset| offset <= i) - 1;
+        let ge_pos = misplaced_ge_from(ge_block) + i - misplaced_ge[ge_block];
+        let less_pos = misplaced_less_from(less_block) + i - misplaced_less[less_block];
+        // SAFETY: все позиции лишних элементов различны
+        unsafe {
+            arr_ref.swap(ge_pos, less_pos);
+        }
+    });
+
+    boundary
+}
+
+/// Последовательно переставить элементы меньше `pivot` в начало слайса.
+/// Возвращает их количество.
+fn partition_less<T: Ord>(arr: &mut [T], pivot: &T) -> usize {
+    let mut m = 0;
+    for i in 0..arr.len() {
+        if arr[i] < *pivot {
+            arr.swap(i, m);
+            m += 1;
+        }
+    }
+    m
+}
+
+/// Посчитать для каждого элемента его позицию среди подходящих
+/// (невключительные префиксные суммы по маске) и количество подходящих элементов.
+fn filter_mask<T: Sync>(
+    arr: &[T],
+    condition: impl Fn(&T) -> bool + Copy + Sync,
+) -> (Vec<i32>, usize) {
+    let mut mask: Vec<i32> = par_map_uninit(arr, |x| if condition(x) { 1 } else { 0 });
+    let filtered_count = par_inline_prefix_sums(&mut mask);
+    (mask, filtered_count as usize)
+}
+
+/// Вызвать `write(i, pos)` для каждого подходящего элемента `i`,
+/// где `pos` - его позиция среди подходящих, посчитанная в [filter_mask].
+/// Элемент подходит, если на нем префиксная сумма увеличивается.
+fn filter_scatter(mask: &[i32], filtered_count: usize, write: impl Fn(usize, usize) + Copy + Sync) {
+    par_for_range(0, mask.len(), |i| {
+        let next_pos = mask.get(i + 1).map_or(filtered_count, |&pos| pos as usize);
+        if next_pos != mask[i] as usize {
+            write(i, mask[i] as usize);
+        }
+    });
+}
+
+///////////////////
+// Any / All
+///////////////////
+
+const SEARCH_BLOCK_SIZE: usize = 4096;
+
+/// Параллельно проверить, что хотя бы один элемент удовлетворяет условию. O(log n) span.
+/// Как только подходящий элемент найден, остальные задачи прекращают работу
+/// на границе ближайшего блока.
+pub fn par_any<T: Sync>(arr: &[T], pred: impl Fn(&T) -> bool + Copy + Sync) -> bool {
+    let found = AtomicBool::new(false);
+    par_any_helper(arr, &found, pred);
+    found.load(Ordering::Relaxed)
+}
+
+/// Параллельно проверить, что все элементы удовлетворяют условию. O(log n) span.
+/// Прекращает работу, как только найден неподходящий элемент.
+pub fn par_all<T: Sync>(arr: &[T], pred: impl Fn(&T) -> bool + Copy + Sync) -> bool {
+    !par_any(arr, |x| !pred(x))
+}
+
+/// Параллельно проверить, что все элементы массива равны между собой. O(log n) span.
+/// Прекращает работу, как только найден элемент, отличный от первого.
+/// Для пустого массива и массива из одного элемента возвращает `true`.
+pub fn par_all_equal<T: PartialEq + Sync>(arr: &[T]) -> bool {
+    match arr.first() {
+        Some(first) => par_all(arr, |x| x == first),
+        None => true,
+    }
+}
+
+fn par_any_helper<T: Sync>(arr: &[T], found: &AtomicBool, pred: impl Fn(&T) -> bool + Copy + Sync) {
+    if found.load(Ordering::Relaxed) {
+        return;
+    }
+    if arr.len() <= SEARCH_BLOCK_SIZE {
+        if arr.iter().any(pred) {
+            found.store(true, Ordering::Relaxed);
+        }
+        return;
+    }
+    let (left, right) = arr.split_at(arr.len() / 2);
+    join(
+        || par_any_helper(left, found, pred),
+        || par_any_helper(right, found, pred),
+    );
+}
+
+/// Параллельно проверить слайсы на равенство. O(log n) span.
+/// Прекращает работу, как только найден блок с различающимися элементами.
+pub fn par_eq<T: PartialEq + Sync>(a: &[T], b: &[T]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let differs = AtomicBool::new(false);
+    par_eq_helper(a, b, &differs);
+    !differs.load(Ordering::Relaxed)
+}
+
+fn par_eq_helper<T: PartialEq + Sync>(a: &[T], b: &[T], differs: &AtomicBool) {
+    if differs.load(Ordering::Relaxed) {
+        return;
+    }
+    if a.len() <= SEARCH_BLOCK_SIZE {
+        if a != b {
+            differs.store(true, Ordering::Relaxed);
+        }
+        return;
+    }
+    let m = a.len() / 2;
+    let (a_left, a_right) = a.split_at(m);
+    let (b_left, b_right) = b.split_at(m);
+    join(
+        || par_eq_helper(a_left, b_left, differs),
+        || par_eq_helper(a_right, b_right, differs),
+    );
+}
+
+/// Параллельно проверить, что слайс отсортирован по неубыванию. O(log n) span.
+/// Прекращает работу, как только найдена пара соседних элементов в неправильном порядке.
+pub fn par_is_sorted<T: Ord + Sync>(arr: &[T]) -> bool {
+    let unsorted = AtomicBool::new(false);
+    par_is_sorted_helper(arr, &unsorted);
+    !unsorted.load(Ordering::Relaxed)
+}
+
+fn par_is_sorted_helper<T: Ord + Sync>(arr: &[T], unsorted: &AtomicBool) {
+    if unsorted.load(Ordering::Relaxed) {
+        return;
+    }
+    if arr.len() <= SEARCH_BLOCK_SIZE {
+        if arr.windows(2).any(|pair| pair[0] > pair[1]) {
+            unsorted.store(true, Ordering::Relaxed);
+        }
+        return;
+    }
+    let m = arr.len() / 2;
+    // Пару на границе половин проверяем здесь
+    if arr[m - 1] > arr[m] {
+        unsorted.store(true, Ordering::Relaxed);
+        return;
+    }
+    let (left, right) = arr.split_at(m);
+    join(
+        || par_is_sorted_helper(left, unsorted),
+        || par_is_sorted_helper(right, unsorted),
+    );
+}
+
+///////////////////
+// Find
+///////////////////
+
+/// Параллельно найти индекс первого элемента, удовлетворяющего условию. O(log n) span.
+/// Блоки, целиком лежащие правее уже найденного элемента, не просматриваются.
+pub fn par_find<T: Sync>(arr: &[T], pred: impl Fn(&T) -> bool + Copy + Sync) -> Option<usize> {
+    let first = AtomicUsize::new(usize::MAX);
+    par_find_helper(arr, 0, &first, pred);
+    match first.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        i => Some(i),
+    }
+}
+
+fn par_find_helper<T: Sync>(
+    arr: &[T],
+    l: usize,
+    first: &AtomicUsize,
+    pred: impl Fn(&T) -> bool + Copy + Sync,
+) {
+    if first.load(Ordering::Relaxed) < l {
+        return;
+    }
+    if arr.len() <= SEARCH_BLOCK_SIZE {
+        if let Some(i) = arr.iter().position(pred) {
+            first.fetch_min(l + i, Ordering::Relaxed);
+        }
+        return;
+    }
+    let (left, right) = arr.split_at(arr.len() / 2);
+    let right_left = l + left.len();
+    join(
+        || par_find_helper(left, l, first, pred),
+        || par_find_helper(right, right_left, first, pred),
+    );
+}
+
+///////////////////
+// Min / Max
+///////////////////
+
+/// Параллельно найти минимум и максимум за один проход по массиву.
+/// Для пустого массива возвращает `None`. O(log n) span
+pub fn par_min_max<T: Ord + Copy + Sync>(arr: &[T]) -> Option<(T, T)> {
+    if arr.is_empty() {
+        return None;
+    }
+    let (min, max) = par_min_max_helper(arr, 0);
+    Some((arr[min], arr[max]))
+}
+
+/// Параллельно найти индекс минимума, при равенстве - наименьший.
+/// Для пустого массива возвращает `None`. O(log n) span
+pub fn par_argmin<T: Ord + Sync>(arr: &[T]) -> Option<usize> {
+    (!arr.is_empty()).then(|| par_min_max_helper(arr, 0).0)
+}
+
+/// Параллельно найти индекс максимума, при равенстве - наименьший
+/// (в отличие от [Iterator::max_by_key], который возвращает последний).
+/// Для пустого массива возвращает `None`. O(log n) span
+pub fn par_argmax<T: Ord + Sync>(arr: &[T]) -> Option<usize> {
+    (!arr.is_empty()).then(|| par_min_max_helper(arr, 0).1)
+}
+
+/// Найти индексы минимума и максимума непустого массива `arr[l..]`.
+/// Между задачами передаются индексы, поэтому от `T` не требуется `Send`.
+fn par_min_max_helper<T: Ord + Sync>(arr: &[T], l: usize) -> (usize, usize) {
+    if arr.len() <= 4096 {
+        let (min, max) = arr
+            .iter()
+            .enumerate()
+            .skip(1)
+            .fold((0, 0), |(min, max), (i, x)| {
+                (
+                    if *x < arr[min] { i } else { min },
+                    if *x > arr[max] { i } else { max },
+                )
+            });
+        return (l + min, l + max);
+    }
+    let m = arr.len() / 2;
+    let ((left_min, left_max), (right_min, right_max)) = join(
+        || par_min_max_helper(&arr[..m], 0),
+        || par_min_max_helper(&arr[m..], m),
+    );
+    let min = if arr[right_min] < arr[left_min] {
+        right_min
+    } else {
+        left_min
+    };
+    let max = if arr[right_max] > arr[left_max] {
+        right_max
+    } else {
+        left_max
+    };
+    (l + min, l + max)
+}
+
+///////////////////
+// Binary search
+///////////////////
+
+/// Параллельно найти для каждого запроса его нижнюю границу в отсортированном слайсе:
+/// количество элементов, строго меньших запроса (аналог [slice::partition_point]).
+/// O(log n) span, O(q log n) work
+pub fn par_lower_bound_batch<T: Ord + Sync>(sorted: &[T], queries: &[T]) -> Vec<usize> {
+    par_map_uninit(queries, |query| sorted.partition_point(|x| x < query))
+}
+
+/// Параллельно найти каждый запрос в отсортированном слайсе интерполяционным поиском.
+/// Возвращает индекс первого вхождения запроса или `None`, если его нет.
+///
+/// На примерно равномерно распределенных данных позиция пробы, вычисленная
+/// линейной интерполяцией, почти сразу попадает в цель: O(log log n) проб
+/// вместо O(log n). Если несколько проб подряд плохо сужают отрезок поиска,
+/// поиск продолжается бинарным.
+pub fn par_interpolation_search_batch<T: Ord + Copy + Sync + ToPrimitive>(
+    sorted: &[T],
+    queries: &[T],
+) -> Vec<Option<usize>> {
+    par_map_uninit(queries, |&query| {
+        let pos = interpolation_lower_bound(sorted, query);
+        (pos < sorted.len() && sorted[pos] == query).then_some(pos)
+    })
+}
+
+/// Нижняя граница `query` в отсортированном слайсе, найденная интерполяционным поиском.
+fn interpolation_lower_bound<T: Ord + Copy + ToPrimitive>(sorted: &[T], query: T) -> usize {
+    const MAX_BAD_PROBES: usize = 3;
+    // Ответ всегда лежит в [l, r]
+    let (mut l, mut r) = (0, sorted.len());
+    let mut bad_probes = 0;
+    while l < r && bad_probes < MAX_BAD_PROBES {
+        let (first, last) = (sorted[l], sorted[r - 1]);
+        if query <= first {
+            return l;
+        }
+        if query > last {
+            return r;
+        }
+        let (Some(query_f), Some(first_f), Some(last_f)) =
+            (query.to_f64(), first.to_f64(), last.to_f64())
+        else {
+            break;
+        };
+        // Здесь sorted[l] < query <= sorted[r - 1], поэтому проба в [l + 1, r - 1]
+        let offset = (query_f - first_f) / (last_f - first_f) * (r - 1 - l) as f64;
+        let probe = (l + offset as usize).clamp(l + 1, r - 1);
+        let len_before = r - l;
+        if sorted[probe] < query {
+            l = probe + 1;
+        } else {
+            r = probe;
+        }
+        if 2 * (r - l) > len_before {
+            bad_probes += 1;
+        }
+    }
+    l + sorted[l..r].partition_point(|&x| x < query)
+}
+
+///////////////////
+// Merge
+///////////////////
+
+/// Параллельно слить отсортированные слайсы `a` и `b` в `dst` длины `a.len() + b.len()`.
+/// Слияние устойчиво: при равенстве раньше идут элементы `a`.
+/// Средний элемент большего слайса делит оба слайса бинарным поиском,
+/// после чего половины сливаются независимо. O(log^2 n) span
+pub fn par_merge<T: Ord + Copy + Send + Sync>(a: &[T], b: &[T], dst: &mut [T]) {
+    assert_eq!(a.len() + b.len(), dst.len());
+    if dst.len() <= 4096 {
+        merge_to(a, b, dst);
+        return;
+    }
+    let (a_split, b_split) = if a.len() >= b.len() {
+        let a_split = a.len() / 2;
+        (a_split, b.partition_point(|x| *x < a[a_split]))
+    } else {
+        let b_split = b.len() / 2;
+        (a.partition_point(|x| *x <= b[b_split]), b_split)
+    };
+    let (a_left, a_right) = a.split_at(a_split);
+    let (b_left, b_right) = b.split_at(b_split);
+    let (dst_left, dst_right) = dst.split_at_mut(a_split + b_split);
+    join(
+        || par_merge(a_left, b_left, dst_left),
+        || par_merge(a_right, b_right, dst_right),
+    );
+}
+
+fn merge_to<T: Ord + Copy>(a: &[T], b: &[T], dst: &mut [T]) {
+    let (mut i, mut j) = (0, 0);
+    for x in dst {
+        if j == b.len() || (i < a.len() && a[i] <= b[j]) {
+            *x = a[i];
+            i += 1;
+        } else {
+            *x = b[j];
+            j += 1;
+        }
+    }
+}
+
+///////////////////
+// Histogram
+///////////////////
+
+/// Параллельно построить гистограмму: `bin_of` задает номер корзины (меньше `bins`)
+/// для каждого элемента. Каждая задача считает свою локальную гистограмму,
+/// затем они складываются, поэтому общий массив счетчиков не разделяется между потоками.
+/// O(bins * log n) span.
+pub fn par_histogram<T: Sync>(
+    arr: &[T],
+    bins: usize,
+    bin_of: impl Fn(&T) -> usize + Copy + Sync,
+) -> Vec<usize> {
+    if arr.len() <= 4096 {
+        let mut histogram = vec![0; bins];
+        arr.iter().for_each(|x| histogram[bin_of(x)] += 1);
+        return histogram;
+    }
+    let (left, right) = arr.split_at(arr.len() / 2);
+    let (mut left_histogram, right_histogram) = join(
+        || par_histogram(left, bins, bin_of),
+        || par_histogram(right, bins, bin_of),
+    );
+    left_histogram
+        .iter_mut()
+        .zip(right_histogram)
+        .for_each(|(l, r)| *l += r);
+    left_histogram
+}
+
+/// Параллельно посчитать количество элементов, удовлетворяющих условию,
+/// в каждом куске из `block` элементов (последний кусок может быть неполным).
+/// Помогает увидеть, насколько неравномерно подходящие элементы распределены по массиву.
+/// Как и в [par_chunk_reduce], счетчики раздаются блоками через [blocked_for_dyn].
+pub fn par_block_counts<T: Sync>(
+    arr: &[T],
+    block: usize,
+    pred: impl Fn(&T) -> bool + Copy + Sync,
+) -> Vec<usize> {
+    assert!(block > 0);
+    let mut counts = vec![0; arr.len().div_ceil(block)];
+    let counts_per_task = cmp::max(1, SCAN_BLOCK_SIZE / block);
+    blocked_for_dyn(&mut counts, counts_per_task, |task_num, task_counts| {
+        let first_block = task_num * counts_per_task;
+        for (i, count) in task_counts.iter_mut().enumerate() {
+            let from = (first_block + i) * block;
+            let to = cmp::min(from + block, arr.len());
+            *count = arr[from..to].iter().filter(|x| pred(x)).count();
+        }
+    });
+    counts
+}
+
+/// Параллельно посчитать соседние пары `(arr[i], arr[i + 1])`, удовлетворяющие условию,
+/// например спуски `arr[i] > arr[i + 1]`. Каждая пара учитывается ровно один раз. O(log n) span
+pub fn par_count_adjacent<T: Sync>(
+    arr: &[T],
+    pred: impl Fn(&T, &T) -> bool + Copy + Sync,
+) -> usize {
+    if arr.len() <= 4096 {
+        return arr.windows(2).filter(|w| pred(&w[0], &w[1])).count();
+    }
+    let m = arr.len() / 2;
+    let (left, right) = arr.split_at(m);
+    let (left_count, right_count) = join(
+        || par_count_adjacent(left, pred),
+        || par_count_adjacent(right, pred),
+    );
+    // Пара на стыке половин не попадает ни в одну из них
+    left_count + right_count + pred(&arr[m - 1], &arr[m]) as usize
+}
+
+///////////////////
+// Reverse / Rotate / Swap
+///////////////////
+
+/// Параллельно развернуть слайс (аналог [slice::reverse]). O(log n) span
+pub fn par_reverse<T: Send>(arr: &mut [T]) {
+    let half = arr.len() / 2;
+    let (left, rest) = arr.split_at_mut(half);
+    let right_start = rest.len() - half;
+    par_reverse_helper(left, &mut rest[right_start..]);
+}
+
+/// Обменять `left[i]` с `right[right.len() - 1 - i]` для всех i.
+fn par_reverse_helper<T: Send>(left: &mut [T], right: &mut [T]) {
+    if left.len() <= 4096 {
+        left.iter_mut()
+            .zip(right.iter_mut().rev())
+            .for_each(|(a, b)| core::mem::swap(a, b));
+        return;
+    }
+    let m = left.len() / 2;
+    let (left_left, left_right) = left.split_at_mut(m);
+    let (right_left, right_right) = right.split_at_mut(right.len() - m);
+    join(
+        || par_reverse_helper(left_left, right_right),
+        || par_reverse_helper(left_right, right_left),
+    );
+}
+
+/// Параллельно поменять местами содержимое двух непересекающихся слайсов одинаковой длины
+/// (аналог [slice::swap_with_slice]). O(log n) span
+pub fn par_swap_ranges<T: Send>(a: &mut [T], b: &mut [T]) {
+    assert_eq!(a.len(), b.len());
+    if a.len() <= 4096 {
+        a.swap_with_slice(b);
+        return;
+    }
+    let m = a.len() / 2;
+    let (a_left, a_right) = a.split_at_mut(m);
+    let (b_left, b_right) = b.split_at_mut(m);
+    join(
+        || par_swap_ranges(a_left, b_left),
+        || par_swap_ranges(a_right, b_right),
+    );
+}
+
+/// Параллельно циклически сдвинуть слайс влево на `k` позиций (аналог [slice::rotate_left]).
+/// Используется трюк с тремя разворотами: (A B) -> (A^r B^r)^r = (B A). O(log n) span
+pub fn par_rotate_left<T: Copy + Send + Sync>(arr: &mut [T], k: usize) {
+    if arr.is_empty() {
+        return;
+    }
+    let k = k % arr.len();
+    if k == 0 {
+        return;
+    }
+    let (a, b) = arr.split_at_mut(k);
+    join(|| par_reverse(a), || par_reverse(b));
+    par_reverse(arr);
+}
+
+///////////////////
+// Shuffle
+///////////////////
+
+/// Случайно перемешать слайс. Половины перемешиваются параллельно независимыми генераторами,
+/// затем сливаются MergeShuffle (Bacher et al.): при равномерно перемешанных половинах
+/// результат - равномерная перестановка, но не та же, что дает последовательный Фишер-Йетс
+/// с тем же генератором. Слияние последовательное, поэтому O(n) span
+pub fn par_shuffle<T: Send>(arr: &mut [T], rng: &mut Random) {
+    if arr.len() <= 4096 {
+        for i in (1..arr.len()).rev() {
+            arr.swap(i, rng.next_i64_in_range(0, i as i64 + 1) as usize);
+        }
+        return;
+    }
+    let m = arr.len() / 2;
+    let (left, right) = arr.split_at_mut(m);
+    let mut left_rng = Random::new(rng.next());
+    let mut right_rng = Random::new(rng.next());
+    join(
+        || par_shuffle(left, &mut left_rng),
+        || par_shuffle(right, &mut right_rng),
+    );
+    merge_shuffle(arr, m, rng);
+}
+
+/// Слить перемешанные `arr[..m]` и `arr[m..]` в равномерно перемешанный `arr`.
+fn merge_shuffle<T>(arr: &mut [T], m: usize, rng: &mut Random) {
+    let (mut i, mut j) = (0, m);
+    loop {
+        if rng.next() & 1 == 0 {
+            if j == arr.len() {
+                break;
+            }
+            arr.swap(i, j);
+            j += 1;
+        } else if i == j {
+            break;
+        }
+        i += 1;
+    }
+    // Оставшиеся элементы вставляются на случайные позиции, как в Фишере-Йетсе
+    for k in i..arr.len() {
+        arr.swap(k, rng.next_i64_in_range(0, k as i64 + 1) as usize);
+    }
+}
+
+///////////////////
+// Flatten
+///////////////////
+
+/// Параллельно склеить набор векторов в один (аналог [slice::concat]).
+/// Смещения векторов в результате считаются префиксными суммами по их длинам,
+/// после чего элементы каждого вектора записываются на свои места сразу
+/// в неинициализированную память результата. O(log n) span
+pub fn par_flatten<T: Copy + Send + Sync>(chunks: &[Vec<T>]) -> Vec<T> {
+    let mut offsets: Vec<usize> = par_map(chunks, |chunk| chunk.len());
+    let total_len = par_inline_prefix_sums(&mut offsets);
+
+    let mut res: Vec<T> = Vec::with_capacity(total_len);
+    let res_ref = UnsafeSlice::new(&mut res.spare_capacity_mut()[..total_len]);
+    par_flatten_helper(chunks, &offsets, res_ref);
+    // SAFETY: векторы занимают непересекающиеся отрезки [offsets[i], offsets[i] + len),
+    // покрывающие все первые total_len позиций
+    unsafe {
+        res.set_len(total_len);
+    }
+    res
+}
+
+fn par_flatten_helper<T: Copy + Send + Sync>(
+    chunks: &[Vec<T>],
+    offsets: &[usize],
+    dst: UnsafeSlice<MaybeUninit<T>>,
+) {
+    match chunks {
+        [] => {}
+        [chunk] => par_for_range(0, chunk.len(), |i| unsafe {
+            dst.write(offsets[0] + i, MaybeUninit::new(chunk[i]));
+        }),
+        _ => {
+            let m = chunks.len() / 2;
+            join(
+                || par_flatten_helper(&chunks[..m], &offsets[..m], dst),
+                || par_flatten_helper(&chunks[m..], &offsets[m..], dst),
+            );
+        }
+    }
+}
+
+///////////////////
+// Interleave
+///////////////////
+
+/// Параллельно перемежить два слайса одинаковой длины: `[a0, b0, a1, b1, ...]`
+/// (переход от структуры массивов к массиву структур). O(log n) span
+pub fn par_interleave<T: Copy + Send + Sync>(a: &[T], b: &[T]) -> Vec<T> {
+    assert_eq!(a.len(), b.len());
+    let len = 2 * a.len();
+    let mut res = Vec::with_capacity(len);
+    par_for(&mut res.spare_capacity_mut()[..len], |i, x| {
+        x.write(if i % 2 == 0 { a[i / 2] } else { b[i / 2] });
+    });
+    // SAFETY: par_for записал все len элементов
+    unsafe { res.set_len(len) };
+    res
+}
+
+/// Параллельно разделить слайс четной длины на элементы с четными и нечетными индексами,
+/// обратное к [par_interleave]. O(log n) span
+pub fn par_deinterleave<T: Copy + Send + Sync>(arr: &[T]) -> (Vec<T>, Vec<T>) {
+    assert!(arr.len() % 2 == 0);
+    let half = arr.len() / 2;
+    let take_every_second = |offset: usize| {
+        let mut res = Vec::with_capacity(half);
+        par_for(&mut res.spare_capacity_mut()[..half], |i, x| {
+            x.write(arr[2 * i + offset]);
+        });
+        // SAFETY: par_for записал все half элементов
+        unsafe { res.set_len(half) };
+        res
+    };
+    join(|| take_every_second(0), || take_every_second(1))
+}
+
+///////////////////
+// Run-length encoding
+///////////////////
+
+/// Параллельно сжать отсортированный слайс: вернуть различные значения
+/// и количество вхождений каждого из них.
+/// Начала серий отмечаются маской границ и сжимаются префиксными суммами,
+/// после чего длина каждой серии считается как разность соседних начал. O(log n) span
+pub fn par_run_length_encode<T: Ord + Copy + Send + Sync>(sorted: &[T]) -> (Vec<T>, Vec<usize>) {
+    let starts = run_starts(sorted);
+    let values = par_map_uninit(&starts, |&start| sorted[start]);
+    let mut counts = vec![0; starts.len()];
+    par_for(&mut counts, |run, count| {
+        *count = starts.get(run + 1).map_or(sorted.len(), |&end| end) - starts[run];
+    });
+    (values, counts)
+}
+
+/// Параллельно разбить отсортированный слайс на максимальные серии равных элементов
+/// и вернуть полуинтервалы `(start, end)` этих серий. O(log n) span
+pub fn par_group_ranges<T: Ord + Sync>(sorted: &[T]) -> Vec<(usize, usize)> {
+    let starts = run_starts(sorted);
+    par_tabulate(starts.len(), |run| {
+        (
+            starts[run],
+            starts.get(run + 1).map_or(sorted.len(), |&end| end),
+        )
+    })
+}
+
+/// Параллельно удалить подряд идущие повторы (аналог [Vec::dedup]).
+/// Начала серий находятся через маску границ и префиксные суммы, оставляемые элементы
+/// собираются во временный буфер, копируются в начало вектора, и вектор обрезается.
+/// O(log n) span
+pub fn par_dedup_in_place<T: Ord + Copy + Send + Sync>(v: &mut Vec<T>) {
+    let starts = run_starts(v);
+    let kept = par_gather(v, &starts);
+    par_copy(&mut v[..kept.len()], &kept);
+    v.truncate(kept.len());
+}
+
+/// Индексы начал серий равных элементов, найденные маской границ и префиксными суммами
+fn run_starts<T: Ord + Sync>(sorted: &[T]) -> Vec<usize> {
+    let mut mask = vec![0; sorted.len()];
+    par_for(&mut mask, |i, is_start| {
+        *is_start = (i == 0 || sorted[i - 1] != sorted[i]) as i32;
+    });
+    let runs_count = par_inline_prefix_sums(&mut mask) as usize;
+
+    let mut starts = vec![0; runs_count];
+    let starts_ref = UnsafeSlice::new(&mut starts);
+    filter_scatter(&mask, runs_count, |i, pos| unsafe {
+        starts_ref.write(pos, i);
+    });
+    starts
+}
+
+/// Параллельно посчитать количество различных значений в отсортированном слайсе,
+/// не строя их: это количество границ `arr[i - 1] != arr[i]` плюс один для непустого слайса.
+/// O(log n) span
+pub fn par_distinct_count<T: Ord + Sync>(sorted: &[T]) -> usize {
+    if sorted.is_empty() {
+        return 0;
+    }
+    1 + par_count_adjacent(sorted, |a, b| a != b)
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Random;
+
+    #[test]
+    fn par_for_test() {
+        let mut arr = vec![1, 2, 3, 4, 5];
+        par_for(&mut arr, |i, x| *x += 2 * i);
+        assert_eq!(vec![1, 4, 7, 10, 13], arr);
+    }
+
+    #[test]
+    fn par_for_numa_test() {
+        for len in [0, 1, 3, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = vec![1; len];
+            par_for_numa(&mut arr, |i, x| *x += 2 * i);
+            let expected: Vec<usize> = (0..len).map(|i| 1 + 2 * i).collect();
+            assert_eq!(expected, arr);
+        }
+
+        // Длина не делится на количество потоков, последний кусок короче остальных
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let mut arr = vec![0; 10_000];
+        pool.install(|| par_for_numa(&mut arr, |i, x| *x = i));
+        assert_eq!((0..10_000).collect::<Vec<usize>>(), arr);
+    }
+
+    #[test]
+    fn par_for_blocked_test() {
+        for block in [1, usize::MAX] {
+            let mut arr = vec![0; 10_000];
+            par_for_blocked(&mut arr, block, |i, x| *x = 3 * i);
+            let expected: Vec<usize> = (0..10_000).map(|i| 3 * i).collect();
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn blocked_for_test() {
+        let len = 10_007;
+        let mut arr = vec![0; len];
+        blocked_for::<_, 1000>(&mut arr, |block_num, block| {
+            assert!(block.len() <= 1000);
+            block
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, x)| *x = 1000 * block_num + i);
+        });
+        let expected: Vec<usize> = (0..len).collect();
+        assert_eq!(expected, arr);
+    }
+
+    #[test]
+    fn blocked_for_dyn_test() {
+        let len = 10_007;
+        for block_size in [1, 3, 1000, 4096, len - 1, len, 2 * len] {
+            let mut arr = vec![0; len];
+            blocked_for_dyn(&mut arr, block_size, |block_num, block| {
+                assert!(block.len() <= block_size);
+                block.iter_mut().for_each(|x| *x = block_num);
+            });
+            let expected: Vec<usize> = (0..len).map(|i| i / block_size).collect();
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn blocked_for_split_boundaries_test() {
+        fn check(block_size: usize, arr: &[usize]) {
+            let expected: Vec<usize> = (0..arr.len()).map(|i| i / block_size).collect();
+            assert_eq!(expected, arr);
+        }
+
+        const BLOCK: usize = 1000;
+        for blocks in [1, 2, 3, 7, 8, 9, 16] {
+            for len in [blocks * BLOCK - 1, blocks * BLOCK, blocks * BLOCK + 1] {
+                let mut arr = vec![usize::MAX; len];
+                blocked_for::<_, BLOCK>(&mut arr, |block_num, block| {
+                    assert_eq!(BLOCK.min(len - block_num * BLOCK), block.len());
+                    block.iter_mut().for_each(|x| *x = block_num);
+                });
+                check(BLOCK, &arr);
+
+                let mut arr = vec![usize::MAX; len];
+                blocked_for_dyn(&mut arr, BLOCK, |block_num, block| {
+                    assert_eq!(BLOCK.min(len - block_num * BLOCK), block.len());
+                    block.iter_mut().for_each(|x| *x = block_num);
+                });
+                check(BLOCK, &arr);
+            }
+        }
+    }
+
+    #[test]
+    fn par_map_if_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 10 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, -100, 100);
+            let mut expected = arr.clone();
+            for x in expected.iter_mut() {
+                if *x > 50 {
+                    *x = 50;
+                }
+            }
+
+            let mut actual = arr.clone();
+            par_map_if(&mut actual, |&x| x > 50, |x| *x = 50);
+
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn par_fill_with_test() {
+        for arr_len in [0, 1, 10, 10 * SCAN_BLOCK_SIZE + 5] {
+            let mut expected = vec![0; arr_len];
+            for (i, x) in expected.iter_mut().enumerate() {
+                *x = i * i;
+            }
+
+            let mut arr = vec![7; arr_len];
+            par_fill_with(&mut arr, |i| i * i);
+
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn par_tabulate_test() {
+        for n in [0, 1, 10, 10 * SCAN_BLOCK_SIZE + 5] {
+            let mut expected = vec![];
+            for i in 0..n {
+                expected.push(i * i);
+            }
+
+            assert_eq!(expected, par_tabulate(n, |i| i * i));
+        }
+    }
+
+    #[test]
+    fn par_for_range_test() {
+        let len = 10_007;
+        let mut res = vec![0; len];
+        let res_ref = UnsafeSlice::new(&mut res);
+        par_for_range(3, len, |i| unsafe { res_ref.write(i, 2 * i) });
+        let expected: Vec<usize> = (0..len).map(|i| if i < 3 { 0 } else { 2 * i }).collect();
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn par_copy_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let src = random.next_vec(arr_len);
+            let mut dst = vec![0; arr_len];
+            par_copy(&mut dst, &src);
+            assert_eq!(src, dst);
+        }
+    }
+
+    #[test]
+    fn par_gather_test() {
+        let mut random = Random::new(3);
+        for (src_len, indices_len) in [(0, 0), (1, 10), (1000, 100 * SCAN_BLOCK_SIZE + 5)] {
+            let src = random.next_vec(src_len);
+            let indices: Vec<usize> = (0..indices_len)
+                .map(|_| random.next() as usize % src_len)
+                .collect();
+
+            let expected: Vec<i32> = indices.iter().map(|&i| src[i]).collect();
+
+            assert_eq!(expected, par_gather(&src, &indices));
+        }
+    }
+
+    #[test]
+    fn par_apply_permutation_test() {
+        let arr = vec![10, 20, 30, 40];
+        assert_eq!(
+            vec![30, 10, 40, 20],
+            par_apply_permutation(&arr, &[2, 0, 3, 1])
+        );
+        assert_eq!(Vec::<i32>::new(), par_apply_permutation(&[], &[]));
+    }
+
+    #[test]
+    fn par_scatter_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let src = random.next_vec(arr_len);
+            // Случайная перестановка индексов
+            let mut indices: Vec<usize> = (0..arr_len).collect();
+            for i in (1..arr_len).rev() {
+                indices.swap(i, random.next() as usize % (i + 1));
+            }
+
+            let mut dst = vec![0; arr_len];
+            unsafe { par_scatter(&src, &indices, &mut dst) };
+
+            let mut expected = vec![0; arr_len];
+            for (i, &index) in indices.iter().enumerate() {
+                expected[index] = src[i];
+            }
+            assert_eq!(expected, dst);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn par_scatter_out_of_bounds_test() {
+        let mut dst = vec![0; 3];
+        unsafe { par_scatter(&[1, 2, 3], &[0, 3, 1], &mut dst) };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "scatter indices are not unique")]
+    fn par_scatter_duplicate_indices_test() {
+        let mut dst = vec![0; 3];
+        unsafe { par_scatter(&[1, 2, 3], &[0, 2, 0], &mut dst) };
+    }
+
+    #[test]
+    fn par_for_chunks_test() {
+        let len = 10_007;
+        for chunk_size in [1, 7, 1000, len, 2 * len] {
+            let mut arr = vec![usize::MAX; len];
+            par_for_chunks(&mut arr, chunk_size, |chunk_num, chunk| {
+                chunk.iter_mut().for_each(|x| *x = chunk_num);
+            });
+            for (chunk_num, chunk) in arr.chunks(chunk_size).enumerate() {
+                assert!(chunk.iter().all(|&x| x == chunk_num));
+            }
+        }
+    }
+
+    #[test]
+    fn par_map_test() {
         let arr = vec![1, 2, 3, 4, 5];
         let new_arr = par_map(&arr, |x| 2 * x);
         assert_eq!(vec![2, 4, 6, 8, 10], new_arr);
     }
 
     #[test]
-    fn inline_pref_sums_test() {
+    fn par_map_uninit_test() {
+        /// Тип без `Default`
+        #[derive(Debug, PartialEq)]
+        struct Wrapped(String);
+
+        let arr: Vec<usize> = (0..10 * SCAN_BLOCK_SIZE + 5).collect();
+        let expected: Vec<Wrapped> = arr.iter().map(|x| Wrapped(x.to_string())).collect();
+        assert_eq!(expected, par_map_uninit(&arr, |x| Wrapped(x.to_string())));
+        assert_eq!(
+            Vec::<Wrapped>::new(),
+            par_map_uninit(&[] as &[usize], |x| Wrapped(x.to_string()))
+        );
+    }
+
+    #[test]
+    fn par_map_into_test() {
+        let mut random = Random::new(3);
+        let arr = random.next_vec_in_range(10 * SCAN_BLOCK_SIZE + 5, -100, 100);
+        let mut dst = vec![0; arr.len()];
+
+        par_map_into(&arr, &mut dst, |x| 2 * x);
+        let expected: Vec<i32> = arr.iter().map(|x| 2 * x).collect();
+        assert_eq!(expected, dst);
+
+        par_map_into(&arr, &mut dst, |x| x - 1);
+        let expected: Vec<i32> = arr.iter().map(|x| x - 1).collect();
+        assert_eq!(expected, dst);
+    }
+
+    #[test]
+    fn par_zip_with_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 10 * SCAN_BLOCK_SIZE + 5] {
+            let a = random.next_vec_in_range(arr_len, -100, 100);
+            let b = random.next_vec_in_range(arr_len, -100, 100);
+            let expected: Vec<i32> = a.iter().zip(b.iter()).map(|(x, y)| x + y).collect();
+            assert_eq!(expected, par_zip_with(&a, &b, |x, y| x + y));
+        }
+    }
+
+    #[test]
+    fn par_map_reduce_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, -1000, 1000);
+            let squares: Vec<i64> = par_map(&arr, |&x| x as i64 * x as i64);
+            let expected: i64 = squares.iter().sum();
+
+            assert_eq!(
+                expected,
+                par_map_reduce(&arr, |&x| x as i64 * x as i64, 0, |a, b| a + b)
+            );
+        }
+    }
+
+    #[test]
+    fn par_sum_f64_test() {
+        fn kahan_sum(arr: &[f64]) -> f64 {
+            let mut sum = 0.0;
+            let mut compensation = 0.0;
+            for &x in arr {
+                let y = x - compensation;
+                let t = sum + y;
+                compensation = (t - sum) - y;
+                sum = t;
+            }
+            sum
+        }
+
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr: Vec<f64> = (0..arr_len)
+                .map(|_| random.next() as f64 / u32::MAX as f64 * 1e6)
+                .collect();
+            let expected = kahan_sum(&arr);
+            assert!((par_sum_f64(&arr) - expected).abs() <= 1e-14 * expected.abs());
+        }
+
+        // При сложении слева направо все маленькие слагаемые теряются
+        let mut arr = vec![1e-16; 4_000_000];
+        arr[0] = 1.0;
+        let expected = kahan_sum(&arr);
+        assert_eq!(1.0, arr.iter().sum::<f64>());
+        assert!((par_sum_f64(&arr) - expected).abs() <= 1e-14 * expected);
+    }
+
+    #[test]
+    fn par_chunk_reduce_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 1000, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr: Vec<i64> = random
+                .next_vec_in_range(arr_len, -1000, 1000)
+                .into_iter()
+                .map(i64::from)
+                .collect();
+            let expected: Vec<i64> = arr.chunks(1000).map(|c| c.iter().sum()).collect();
+
+            assert_eq!(expected, par_chunk_reduce(&arr, 1000, 0, |a, b| a + b));
+        }
+        let arr = vec![1; 10];
+        assert_eq!(vec![3, 3, 3, 1], par_chunk_reduce(&arr, 3, 0, |a, b| a + b));
+    }
+
+    #[test]
+    fn inline_pref_sums_test() {
+        let mut arr = vec![1, 2, 3, 4, 5];
+        let sum = inline_pref_sums(&mut arr, 0);
+        assert_eq!(15, sum);
+        assert_eq!(vec![0, 1, 3, 6, 10], arr);
+    }
+
+    #[test]
+    fn par_inline_prefix_sums_test() {
+        let mut random = Random::new(3);
+        for arr_len in [
+            0,
+            10,
+            SCAN_BLOCK_SIZE,
+            12 * SCAN_BLOCK_SIZE,
+            SCAN_BLOCK_SIZE * SCAN_BLOCK_SIZE * 3 + 5,
+        ] {
+            let mut arr = random.next_vec_in_range(arr_len, -100, 100);
+
+            let mut expected = arr.clone();
+            inline_pref_sums(&mut expected, 0);
+
+            par_inline_prefix_sums(&mut arr);
+
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn par_prefix_sums_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 12 * SCAN_BLOCK_SIZE + 7] {
+            let arr = random.next_vec_in_range(arr_len, -100, 100);
+
+            let mut expected = arr.clone();
+            let expected_sum = inline_pref_sums(&mut expected, 0);
+
+            assert_eq!((expected, expected_sum), par_prefix_sums(&arr));
+        }
+    }
+
+    #[test]
+    fn par_prefix_sums_widening_test() {
+        // Сумма уже первых двух элементов не помещается в i32
+        let arr = vec![i32::MAX; 10 * SCAN_BLOCK_SIZE + 5];
+        let expected: Vec<i64> = (0..arr.len() as i64).map(|i| i * i32::MAX as i64).collect();
+        assert_eq!(expected, par_prefix_sums_widening(&arr));
+
+        let arr = vec![i32::MIN, i32::MIN, 5];
+        assert_eq!(
+            vec![0, i32::MIN as i64, 2 * i32::MIN as i64],
+            par_prefix_sums_widening(&arr)
+        );
+        assert!(par_prefix_sums_widening(&[]).is_empty());
+    }
+
+    #[test]
+    fn par_inline_prefix_sums_with_zero_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr: Vec<usize> = random
+                .next_vec_in_range(arr_len, 0, 100)
+                .into_iter()
+                .map(|x| x as usize)
+                .collect();
+            let mut expected = arr.clone();
+            let expected_sum = inline_pref_sums(&mut expected, 0);
+
+            let sum = par_inline_prefix_sums_with_zero(&mut arr, 0);
+
+            assert_eq!(expected_sum, sum);
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn par_scan_to_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let src: Vec<i64> = random
+                .next_vec_in_range(arr_len, -100, 100)
+                .into_iter()
+                .map(i64::from)
+                .collect();
+            let src_copy = src.clone();
+            let mut expected = src.clone();
+            inline_pref_sums(&mut expected, 0);
+
+            let mut dst = vec![7; arr_len];
+            par_scan_to(&src, &mut dst);
+
+            assert_eq!(src_copy, src);
+            assert_eq!(expected, dst);
+        }
+    }
+
+    #[test]
+    fn par_inclusive_scan_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, SCAN_BLOCK_SIZE, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, -1000, 1000);
+
+            let mut running_max = vec![];
+            let mut running_sum = vec![];
+            for &x in &arr {
+                running_max.push(cmp::max(running_max.last().copied().unwrap_or(i32::MIN), x));
+                running_sum.push(running_sum.last().copied().unwrap_or(0) + x);
+            }
+
+            assert_eq!(running_max, par_inclusive_scan(&arr, i32::MIN, cmp::max));
+            assert_eq!(running_sum, par_inclusive_scan(&arr, 0, |a, b| a + b));
+        }
+    }
+
+    #[test]
+    fn par_segmented_prefix_sums_test() {
         let mut arr = vec![1, 2, 3, 4, 5];
-        let sum = inline_pref_sums(&mut arr);
-        assert_eq!(15, sum);
-        assert_eq!(vec![0, 1, 3, 6, 10], arr);
+        par_segmented_prefix_sums(&mut arr, &[false, false, true, false, false]);
+        assert_eq!(vec![0, 1, 0, 3, 7], arr);
+
+        let mut random = Random::new(3);
+        let arr_len = SCAN_BLOCK_SIZE * SCAN_BLOCK_SIZE + 5;
+        let arr = random.next_vec_in_range(arr_len, -100, 100);
+        let segment_starts = [0, 17, 3 * SCAN_BLOCK_SIZE + 5, arr_len / 2, arr_len - 1];
+        for flags in [
+            (0..arr_len)
+                .map(|i| segment_starts.contains(&i))
+                .collect::<Vec<bool>>(),
+            (0..arr_len)
+                .map(|_| random.next_in_range(0, 10000) == 0)
+                .collect(),
+            vec![false; arr_len],
+        ] {
+            let mut expected = arr.clone();
+            inline_segmented_pref_sums(&mut expected, &flags);
+
+            let mut actual = arr.clone();
+            par_segmented_prefix_sums(&mut actual, &flags);
+
+            assert_eq!(expected, actual);
+        }
     }
 
     #[test]
-    fn par_inline_prefix_sums_test() {
+    fn par_inline_prefix_sums_helper_test() {
         let mut random = Random::new(3);
         for arr_len in [
             0,
             10,
             SCAN_BLOCK_SIZE,
-            12 * SCAN_BLOCK_SIZE,
-            SCAN_BLOCK_SIZE * SCAN_BLOCK_SIZE * 3 + 5,
+            SCAN_BLOCK_SIZE + 1,
+            12 * SCAN_BLOCK_SIZE + 7,
+            100 * SCAN_BLOCK_SIZE + 3,
         ] {
             let mut arr = random.next_vec_in_range(arr_len, -100, 100);
 
             let mut expected = arr.clone();
-            inline_pref_sums(&mut expected);
+            par_inline_prefix_sums(&mut expected);
 
-            par_inline_prefix_sums(&mut arr);
+            par_inline_prefix_sums_helper(&mut arr, 0);
 
             assert_eq!(expected, arr);
         }
     }
 
+    #[test]
+    fn par_filter_evaluates_condition_once_test() {
+        let arr: Vec<usize> = (0..10 * SCAN_BLOCK_SIZE + 5).collect();
+        let calls = AtomicUsize::new(0);
+        let last_calls = AtomicUsize::new(0);
+        let filtered = par_filter(&arr, |&x| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            if x == arr.len() - 1 {
+                last_calls.fetch_add(1, Ordering::Relaxed);
+            }
+            x % 3 == 1
+        });
+        let expected: Vec<usize> = arr.iter().copied().filter(|&x| x % 3 == 1).collect();
+        assert_eq!(expected, filtered);
+        assert_eq!(arr.len(), calls.load(Ordering::Relaxed));
+        assert_eq!(1, last_calls.load(Ordering::Relaxed));
+    }
+
     #[test]
     fn par_filter_test() {
         let mut random = Random::new(3);
@@ -289,4 +2272,736 @@ mod tests {
             assert_eq!(expected_filtered, actual_filtered);
         }
     }
+
+    #[test]
+    fn par_filter_with_count_test() {
+        let mut random = Random::new(3);
+        let arr = random.next_vec_in_range(100 * SCAN_BLOCK_SIZE + 5, -100, 100);
+        let conditions: [fn(&i32) -> bool; 4] = [|_| true, |_| false, |&x| x > 0, |&x| x % 7 == 3];
+        for condition in conditions {
+            let (filtered, count) = par_filter_with_count(&arr, condition);
+            assert_eq!(filtered.len(), count);
+            assert_eq!(par_filter(&arr, condition), filtered);
+        }
+        assert_eq!((vec![], 0), par_filter_with_count(&[] as &[i32], |_| true));
+    }
+
+    #[test]
+    fn par_copy_if_test() {
+        let mut random = Random::new(3);
+        let arr = random.next_vec_in_range(100 * SCAN_BLOCK_SIZE + 5, -100, 100);
+        let expected = par_filter(&arr, |&x| x % 7 == 3);
+
+        let mut exact = vec![0; expected.len()];
+        assert_eq!(
+            expected.len(),
+            par_copy_if(&arr, &mut exact, |&x| x % 7 == 3)
+        );
+        assert_eq!(expected, exact);
+
+        let mut larger = vec![i32::MAX; expected.len() + 10];
+        assert_eq!(
+            expected.len(),
+            par_copy_if(&arr, &mut larger, |&x| x % 7 == 3)
+        );
+        assert_eq!(expected, larger[..expected.len()]);
+        assert!(larger[expected.len()..].iter().all(|&x| x == i32::MAX));
+
+        assert_eq!(0, par_copy_if(&arr, &mut [], |_| false));
+    }
+
+    #[test]
+    #[should_panic(expected = "destination is too small")]
+    fn par_copy_if_small_dst_test() {
+        let arr: Vec<i32> = (0..100).collect();
+        let mut dst = vec![0; 10];
+        par_copy_if(&arr, &mut dst, |&x| x % 2 == 0);
+    }
+
+    #[test]
+    fn par_filter_single_thread_test() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let mut random = Random::new(3);
+        let arr = random.next_vec_in_range(100 * SCAN_BLOCK_SIZE + 5, -100, 100);
+        let expected = par_filter(&arr, |&x| x % 3 == 0);
+
+        pool.install(|| {
+            assert!(is_single_threaded());
+            assert_eq!(expected, par_filter(&arr, |&x| x % 3 == 0));
+        });
+    }
+
+    #[test]
+    fn filter_zero_sized_test() {
+        let len = 100 * SCAN_BLOCK_SIZE + 5;
+        let arr = vec![(); len];
+
+        assert_eq!(len, par_filter(&arr, |_| true).len());
+        assert_eq!(0, par_filter(&arr, |_| false).len());
+        assert_eq!(len, par_filter_cloned(&arr, |_| true).len());
+        assert_eq!(len, par_map_uninit(&arr, |&x| x).len());
+        let (less, eq, greater) = par_partition3(&arr, |_| cmp::Ordering::Equal);
+        assert_eq!((0, len, 0), (less.len(), eq.len(), greater.len()));
+    }
+
+    #[test]
+    fn par_any_all_test() {
+        let len = 20 * SEARCH_BLOCK_SIZE + 7;
+        for pos in [0, 1, len / 2, len - 2, len - 1] {
+            let mut arr = vec![0; len];
+            arr[pos] = 1;
+            assert!(par_any(&arr, |&x| x == 1));
+            assert!(!par_all(&arr, |&x| x == 0));
+        }
+        let arr = vec![0; len];
+        assert!(!par_any(&arr, |&x| x == 1));
+        assert!(par_all(&arr, |&x| x == 0));
+        assert!(!par_any(&[] as &[i32], |_| true));
+        assert!(par_all(&[] as &[i32], |_| false));
+    }
+
+    #[test]
+    fn par_all_equal_test() {
+        let len = 20 * SEARCH_BLOCK_SIZE + 7;
+        let mut arr = vec![3; len];
+        assert!(par_all_equal(&arr));
+        arr[len - 1] = 4;
+        assert!(!par_all_equal(&arr));
+        arr[len - 1] = 3;
+        arr[0] = 4;
+        assert!(!par_all_equal(&arr));
+        assert!(par_all_equal(&[] as &[i32]));
+        assert!(par_all_equal(&[5]));
+    }
+
+    #[test]
+    fn par_eq_test() {
+        let mut random = Random::new(3);
+        let a = random.next_vec(100 * SEARCH_BLOCK_SIZE + 5);
+        let mut b = a.clone();
+        assert!(par_eq(&a, &b));
+        for pos in [0, a.len() / 2, a.len() - 1] {
+            b[pos] += 1;
+            assert!(!par_eq(&a, &b));
+            b[pos] -= 1;
+        }
+        assert!(!par_eq(&a, &b[1..]));
+        assert!(par_eq::<i32>(&[], &[]));
+    }
+
+    #[test]
+    fn par_is_sorted_test() {
+        let len = 20 * SEARCH_BLOCK_SIZE + 7;
+        let mut arr: Vec<usize> = (0..len).collect();
+        assert!(par_is_sorted(&arr));
+        for pos in [0, SEARCH_BLOCK_SIZE - 1, len / 2 - 1, len - 2] {
+            arr.swap(pos, pos + 1);
+            assert!(!par_is_sorted(&arr));
+            arr.swap(pos, pos + 1);
+        }
+        assert!(par_is_sorted(&[] as &[i32]));
+        assert!(par_is_sorted(&[1]));
+        assert!(par_is_sorted(&vec![5; len]));
+    }
+
+    #[test]
+    fn par_find_test() {
+        let len = 20 * SEARCH_BLOCK_SIZE + 7;
+        let mut arr = vec![0; len];
+        assert_eq!(None, par_find(&arr, |&x| x == 1));
+        for pos in [
+            len - 1,
+            15 * SEARCH_BLOCK_SIZE,
+            3 * SEARCH_BLOCK_SIZE + 1,
+            5,
+            0,
+        ] {
+            arr[pos] = 1;
+            assert_eq!(Some(pos), par_find(&arr, |&x| x == 1));
+        }
+        assert_eq!(None, par_find(&[] as &[i32], |_| true));
+    }
+
+    #[test]
+    fn par_min_max_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec(arr_len);
+            let expected = arr.iter().min().copied().zip(arr.iter().max().copied());
+            assert_eq!(expected, par_min_max(&arr));
+        }
+    }
+
+    #[test]
+    fn par_argmin_argmax_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            for (from, to) in [(i32::MIN, i32::MAX), (-3, 3)] {
+                let arr = random.next_vec_in_range(arr_len, from, to);
+                let expected_min = arr.iter().enumerate().min_by_key(|&(_, x)| x);
+                assert_eq!(expected_min.map(|(i, _)| i), par_argmin(&arr));
+                let expected_max = arr
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|&(i, x)| (x, cmp::Reverse(i)));
+                assert_eq!(expected_max.map(|(i, _)| i), par_argmax(&arr));
+            }
+        }
+        assert_eq!(Some(0), par_argmin(&[5, 5, 5]));
+        assert_eq!(Some(0), par_argmax(&[5, 5, 5]));
+    }
+
+    #[test]
+    fn par_merge_test() {
+        let mut random = Random::new(3);
+        for (a_len, b_len) in [
+            (0, 0),
+            (0, 10),
+            (10, 0),
+            (1, 1),
+            (100_000, 3),
+            (40_000, 70_001),
+        ] {
+            let mut a = random.next_vec_in_range(a_len, -1000, 1000);
+            let mut b = random.next_vec_in_range(b_len, -1000, 1000);
+            a.sort();
+            b.sort();
+            let mut expected = [a.clone(), b.clone()].concat();
+            expected.sort();
+
+            let mut dst = vec![0; a_len + b_len];
+            par_merge(&a, &b, &mut dst);
+
+            assert_eq!(expected, dst);
+        }
+    }
+
+    #[test]
+    fn par_merge_stable_test() {
+        let mut random = Random::new(3);
+        let mut a: Vec<(i32, usize)> = random
+            .next_vec_in_range(50_000, 0, 100)
+            .into_iter()
+            .map(|x| (x, 0))
+            .collect();
+        let mut b: Vec<(i32, usize)> = random
+            .next_vec_in_range(50_000, 0, 100)
+            .into_iter()
+            .map(|x| (x, 1))
+            .collect();
+        a.sort();
+        b.sort();
+
+        // Сравниваем только по ключу, номер слайса показывает, откуда пришел элемент
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        struct ByKey((i32, usize));
+        impl PartialOrd for ByKey {
+            fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for ByKey {
+            fn cmp(&self, other: &Self) -> cmp::Ordering {
+                self.0 .0.cmp(&other.0 .0)
+            }
+        }
+        let a: Vec<ByKey> = a.into_iter().map(ByKey).collect();
+        let b: Vec<ByKey> = b.into_iter().map(ByKey).collect();
+
+        let mut dst = vec![ByKey((0, 0)); a.len() + b.len()];
+        par_merge(&a, &b, &mut dst);
+
+        assert!(dst.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+
+    #[test]
+    fn par_lower_bound_batch_test() {
+        let mut random = Random::new(3);
+        for (arr_len, queries_len) in [(0, 10), (10, 0), (1000, 10 * SCAN_BLOCK_SIZE + 5)] {
+            let mut arr = random.next_vec_in_range(arr_len, -1000, 1000);
+            arr.sort();
+            let queries = random.next_vec_in_range(queries_len, -1100, 1100);
+
+            let expected: Vec<usize> = queries
+                .iter()
+                .map(|query| arr.partition_point(|x| x < query))
+                .collect();
+
+            assert_eq!(expected, par_lower_bound_batch(&arr, &queries));
+        }
+    }
+
+    #[test]
+    fn par_interpolation_search_batch_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            // Четные числа, чтобы нечетные запросы гарантированно отсутствовали
+            let mut arr: Vec<i64> = random
+                .next_vec_in_range(arr_len, 0, 1_000_000)
+                .into_iter()
+                .map(|x| 2 * x as i64)
+                .collect();
+            arr.sort();
+            let mut queries: Vec<i64> = random
+                .next_vec_in_range(10 * SCAN_BLOCK_SIZE, -10, 2_000_010)
+                .into_iter()
+                .map(i64::from)
+                .collect();
+            queries.extend(arr.iter().step_by(100));
+
+            let expected: Vec<Option<usize>> = queries
+                .iter()
+                .map(|query| {
+                    let pos = arr.partition_point(|x| x < query);
+                    (pos < arr.len() && arr[pos] == *query).then_some(pos)
+                })
+                .collect();
+
+            assert_eq!(expected, par_interpolation_search_batch(&arr, &queries));
+        }
+    }
+
+    #[test]
+    fn par_histogram_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, 0, 100);
+
+            let mut expected = vec![0; 10];
+            arr.iter().for_each(|&x| expected[x as usize / 10] += 1);
+
+            assert_eq!(expected, par_histogram(&arr, 10, |&x| x as usize / 10));
+        }
+    }
+
+    #[test]
+    fn par_count_adjacent_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 2, 4097, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, -10, 10);
+            let expected = arr.windows(2).filter(|w| w[0] > w[1]).count();
+            assert_eq!(expected, par_count_adjacent(&arr, |a, b| a > b));
+        }
+        // Каждая пара, в том числе на стыках блоков, считается ровно один раз
+        let len = 100 * SCAN_BLOCK_SIZE + 5;
+        let arr = vec![0; len];
+        assert_eq!(len - 1, par_count_adjacent(&arr, |_, _| true));
+    }
+
+    #[test]
+    fn par_block_counts_test() {
+        let len = 100 * SCAN_BLOCK_SIZE + 5;
+        let arr: Vec<usize> = (0..len).collect();
+        let block = 1000;
+
+        let counts = par_block_counts(&arr, block, |&x| x < len / 2);
+
+        // Подходят только элементы первой половины
+        let expected: Vec<usize> = (0..len.div_ceil(block))
+            .map(|b| (cmp::min((b + 1) * block, len / 2)).saturating_sub(b * block))
+            .collect();
+        assert_eq!(expected, counts);
+        assert_eq!(len / 2, counts.iter().sum::<usize>());
+        assert_eq!(block, counts[0]);
+        assert_eq!(0, *counts.last().unwrap());
+
+        assert!(par_block_counts(&[] as &[i32], 10, |_| true).is_empty());
+    }
+
+    #[test]
+    fn par_partition3_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, -100, 100);
+
+            let (less, eq, greater) = par_partition3(&arr, |x| x.cmp(&7));
+
+            assert_eq!(par_filter(&arr, |&x| x < 7), less);
+            assert_eq!(par_filter(&arr, |&x| x == 7), eq);
+            assert_eq!(par_filter(&arr, |&x| x > 7), greater);
+        }
+    }
+
+    #[test]
+    fn par_partition3_unique_pivot_test() {
+        let len = 100 * SCAN_BLOCK_SIZE + 5;
+        // Все элементы различны
+        let arr: Vec<i64> = (0..len as i64).map(|i| (i * 7919) % len as i64).collect();
+        let pivot = arr[len / 3];
+
+        let (less, eq, greater) = par_partition3(&arr, |x| x.cmp(&pivot));
+
+        assert_eq!(vec![pivot], eq);
+        assert_eq!(par_filter(&arr, |&x| x < pivot), less);
+        assert_eq!(par_filter(&arr, |&x| x > pivot), greater);
+    }
+
+    #[test]
+    fn par_stable_partition_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            // Пары (значение, исходный индекс), чтобы проверить порядок внутри частей
+            let arr: Vec<(i32, usize)> = random
+                .next_vec_in_range(arr_len, -100, 100)
+                .into_iter()
+                .zip(0..)
+                .collect();
+            let (expected_matching, expected_rest): (Vec<(i32, usize)>, Vec<_>) =
+                arr.iter().partition(|&&(x, _)| x % 3 == 0);
+
+            let (actual, split) = par_stable_partition(&arr, |&(x, _)| x % 3 == 0);
+
+            assert_eq!(expected_matching.len(), split);
+            assert_eq!(expected_matching, actual[..split]);
+            assert_eq!(expected_rest, actual[split..]);
+        }
+    }
+
+    #[test]
+    fn par_partition_in_place_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let random_arr = random.next_vec_in_range(arr_len, -100, 100);
+            let equal_arr = vec![5; arr_len];
+            for (arr, pivot) in [
+                (&random_arr, -200),
+                (&random_arr, -50),
+                (&random_arr, 0),
+                (&random_arr, 200),
+                (&equal_arr, 5),
+                (&equal_arr, 6),
+            ] {
+                let mut actual = arr.clone();
+                let boundary = par_partition_in_place(&mut actual, &pivot);
+
+                assert_eq!(arr.iter().filter(|&&x| x < pivot).count(), boundary);
+                assert!(actual[..boundary].iter().all(|&x| x < pivot));
+                assert!(actual[boundary..].iter().all(|&x| x >= pivot));
+                let mut expected = arr.clone();
+                expected.sort();
+                actual.sort();
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn par_positions_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec_in_range(arr_len, -100, 100);
+            let expected: Vec<usize> = arr
+                .iter()
+                .enumerate()
+                .filter(|(_, &x)| x % 3 == 0)
+                .map(|(i, _)| i)
+                .collect();
+
+            assert_eq!(expected, par_positions(&arr, |&x| x % 3 == 0));
+        }
+    }
+
+    #[test]
+    fn par_retain_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = random.next_vec_in_range(arr_len, -100, 100);
+            let mut expected = arr.clone();
+            expected.retain(|&x| x % 3 == 0);
+
+            par_retain(&mut arr, |&x| x % 3 == 0);
+
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn par_retain_few_kept_test() {
+        let mut random = Random::new(3);
+        let mut arr = random.next_vec_in_range(100 * SCAN_BLOCK_SIZE + 5, 0, 1000);
+        let mut expected = arr.clone();
+        expected.retain(|&x| x == 0);
+        assert!(expected.len() < arr.len() / 100);
+
+        par_retain(&mut arr, |&x| x == 0);
+
+        assert_eq!(expected, arr);
+    }
+
+    #[test]
+    fn par_rotate_left_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 10 * SCAN_BLOCK_SIZE + 5] {
+            let arr = random.next_vec(arr_len);
+            for k in [0, 1, 3, arr_len / 2, arr_len, 2 * arr_len + 1] {
+                let mut expected = arr.clone();
+                if arr_len > 0 {
+                    expected.rotate_left(k % arr_len);
+                }
+                let mut actual = arr.clone();
+                par_rotate_left(&mut actual, k);
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+
+    #[test]
+    fn par_shuffle_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 2, 100, 10 * SCAN_BLOCK_SIZE + 5] {
+            let arr: Vec<usize> = (0..arr_len).collect();
+            let mut actual = arr.clone();
+            par_shuffle(&mut actual, &mut random);
+            if arr_len >= 100 {
+                assert_ne!(arr, actual);
+            }
+            actual.sort();
+            assert_eq!(arr, actual);
+        }
+    }
+
+    #[test]
+    fn par_reverse_test() {
+        let mut random = Random::new(3);
+        for arr_len in [
+            0,
+            1,
+            2,
+            11,
+            10 * SCAN_BLOCK_SIZE + 5,
+            10 * SCAN_BLOCK_SIZE + 6,
+        ] {
+            let mut arr = random.next_vec(arr_len);
+            let mut expected = arr.clone();
+            expected.reverse();
+            par_reverse(&mut arr);
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn par_swap_ranges_test() {
+        let mut random = Random::new(3);
+        for half_len in [0, 1, 10, 10 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = random.next_vec(2 * half_len);
+            let mut expected = arr.clone();
+            let (left, right) = expected.split_at_mut(half_len);
+            left.swap_with_slice(right);
+
+            let (left, right) = arr.split_at_mut(half_len);
+            par_swap_ranges(left, right);
+
+            assert_eq!(expected, arr);
+        }
+    }
+
+    #[test]
+    fn par_flatten_test() {
+        let mut random = Random::new(3);
+        let chunks: Vec<Vec<i32>> = (0..1000)
+            .map(|i| {
+                let chunk_len = random.next_in_range(0, if i % 10 == 0 { 10_000 } else { 10 });
+                random.next_vec(chunk_len as usize)
+            })
+            .collect();
+
+        assert_eq!(chunks.concat(), par_flatten(&chunks));
+        assert_eq!(Vec::<i32>::new(), par_flatten::<i32>(&[]));
+        assert_eq!(vec![1, 2], par_flatten(&[vec![], vec![1], vec![], vec![2]]));
+
+        // Тип без Default
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct NoDefault(i32);
+        let chunks: Vec<Vec<NoDefault>> = chunks
+            .iter()
+            .map(|chunk| chunk.iter().map(|&x| NoDefault(x)).collect())
+            .collect();
+        assert_eq!(chunks.concat(), par_flatten(&chunks));
+    }
+
+    #[test]
+    fn par_interleave_test() {
+        let mut random = Random::new(3);
+        for len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let a = random.next_vec(len);
+            let b = random.next_vec(len);
+
+            let interleaved = par_interleave(&a, &b);
+
+            let expected: Vec<i32> = a.iter().zip(&b).flat_map(|(&x, &y)| [x, y]).collect();
+            assert_eq!(expected, interleaved);
+            assert_eq!((a, b), par_deinterleave(&interleaved));
+        }
+    }
+
+    #[test]
+    fn par_run_length_encode_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = random.next_vec_in_range(arr_len, 0, 50);
+            arr.sort();
+
+            let mut expected_values = vec![];
+            let mut expected_counts = vec![];
+            for &x in &arr {
+                if expected_values.last() == Some(&x) {
+                    *expected_counts.last_mut().unwrap() += 1;
+                } else {
+                    expected_values.push(x);
+                    expected_counts.push(1);
+                }
+            }
+
+            assert_eq!(
+                (expected_values, expected_counts),
+                par_run_length_encode(&arr)
+            );
+        }
+    }
+
+    #[test]
+    fn par_group_ranges_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = random.next_vec_in_range(arr_len, 0, 50);
+            arr.sort();
+
+            let mut expected = vec![];
+            let mut start = 0;
+            for i in 1..=arr.len() {
+                if i == arr.len() || arr[i - 1] != arr[i] {
+                    expected.push((start, i));
+                    start = i;
+                }
+            }
+
+            assert_eq!(expected, par_group_ranges(&arr));
+        }
+    }
+
+    #[test]
+    fn par_dedup_in_place_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = random.next_vec_in_range(arr_len, 0, 50_000);
+            arr.sort();
+            let mut expected = arr.clone();
+            expected.dedup();
+
+            par_dedup_in_place(&mut arr);
+
+            assert_eq!(expected.len(), arr.len());
+            assert_eq!(expected, arr);
+        }
+
+        let mut arr = vec![5; 10_000];
+        par_dedup_in_place(&mut arr);
+        assert_eq!(vec![5], arr);
+    }
+
+    #[test]
+    fn par_distinct_count_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100 * SCAN_BLOCK_SIZE + 5] {
+            let mut arr = random.next_vec_in_range(arr_len, 0, 50_000);
+            arr.sort();
+            let mut distinct = arr.clone();
+            distinct.dedup();
+
+            assert_eq!(distinct.len(), par_distinct_count(&arr));
+        }
+    }
+
+    #[test]
+    fn empty_and_singleton_test() {
+        let mut empty: Vec<i32> = vec![];
+        let mut single = vec![5];
+
+        par_for(&mut empty, |_, _| unreachable!());
+        par_for(&mut single, |i, x| *x += i as i32 + 1);
+        assert_eq!(vec![6], single);
+        single[0] = 5;
+
+        par_for_blocked(&mut empty, 1, |_, _| unreachable!());
+        par_for_range(0, 0, |_| unreachable!());
+        blocked_for::<_, 4>(&mut empty, |_, _| unreachable!());
+        blocked_for_dyn(&mut empty, 4, |_, _| unreachable!());
+        par_for_chunks(&mut empty, 4, |_, _| unreachable!());
+        blocked_for_dyn(&mut single, 4, |block_num, block| {
+            assert_eq!((0, &mut [5][..]), (block_num, block))
+        });
+
+        par_copy(&mut empty, &[]);
+        let mut dst = vec![0];
+        par_copy(&mut dst, &single);
+        assert_eq!(single, dst);
+
+        assert_eq!(Vec::<i32>::new(), par_map(&empty, |x| x + 1));
+        assert_eq!(vec![6], par_map(&single, |x| x + 1));
+        assert_eq!(Vec::<i32>::new(), par_map_uninit(&empty, |x| x + 1));
+        assert_eq!(vec![6], par_map_uninit(&single, |x| x + 1));
+        par_map_into(&empty, &mut [], |x| x + 1);
+        assert_eq!(
+            Vec::<i32>::new(),
+            par_zip_with(&empty, &empty, |x, y| x + y)
+        );
+        assert_eq!(vec![10], par_zip_with(&single, &single, |x, y| x + y));
+
+        assert_eq!(0, par_inline_prefix_sums(&mut empty));
+        let mut arr = single.clone();
+        assert_eq!(5, par_inline_prefix_sums(&mut arr));
+        assert_eq!(vec![0], arr);
+        par_segmented_prefix_sums(&mut empty, &[]);
+        let mut arr = single.clone();
+        par_segmented_prefix_sums(&mut arr, &[true]);
+        assert_eq!(vec![0], arr);
+        assert_eq!((vec![], 0), par_prefix_sums(&empty));
+        assert_eq!((vec![0], 5), par_prefix_sums(&single));
+
+        assert_eq!(Vec::<i32>::new(), par_filter(&empty, |_| true));
+        assert_eq!(single, par_filter(&single, |_| true));
+        assert_eq!(Vec::<i32>::new(), par_filter(&single, |_| false));
+        assert_eq!(Vec::<i32>::new(), par_filter_cloned(&empty, |_| true));
+        assert_eq!(single, par_filter_cloned(&single, |_| true));
+        par_retain(&mut empty, |_| true);
+        let mut arr = single.clone();
+        par_retain(&mut arr, |_| false);
+        assert_eq!(Vec::<i32>::new(), arr);
+        assert_eq!(
+            (vec![], vec![], vec![]),
+            par_partition3(&empty, |x| x.cmp(&5))
+        );
+        assert_eq!(
+            (vec![], vec![5], vec![]),
+            par_partition3(&single, |x| x.cmp(&5))
+        );
+
+        assert!(!par_any(&empty, |_| true));
+        assert!(par_any(&single, |&x| x == 5));
+        assert!(par_all(&empty, |_| false));
+        assert!(par_all(&single, |&x| x == 5));
+        assert!(par_eq(&empty, &[]));
+        assert!(par_eq(&single, &[5]));
+        assert!(par_is_sorted(&empty));
+        assert!(par_is_sorted(&single));
+        assert_eq!(None, par_find(&empty, |_| true));
+        assert_eq!(Some(0), par_find(&single, |&x| x == 5));
+        assert_eq!(vec![0, 0], par_histogram(&empty, 2, |_| 0));
+        assert_eq!(vec![0, 1], par_histogram(&single, 2, |_| 1));
+
+        par_reverse(&mut empty);
+        par_reverse(&mut single);
+        assert_eq!(vec![5], single);
+        par_rotate_left(&mut empty, 3);
+        par_rotate_left(&mut single, 3);
+        assert_eq!(vec![5], single);
+
+        assert_eq!(Vec::<i32>::new(), par_flatten::<i32>(&[]));
+        assert_eq!(single, par_flatten(&[single.clone()]));
+
+        assert!(empty.is_empty());
+    }
 }
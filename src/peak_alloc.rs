@@ -0,0 +1,111 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///////////////////
+// PeakAlloc
+///////////////////
+
+/// Обертка над системным аллокатором, отслеживающая текущий объем выделенной памяти
+/// и его максимум (high-water mark). Регистрируется как `#[global_allocator]`.
+pub struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl PeakAlloc {
+    pub const fn new() -> Self {
+        Self {
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Сколько байт выделено сейчас
+    pub fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Максимум выделенной памяти в байтах с последнего [PeakAlloc::reset_peak]
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Начать отсчет максимума заново с текущего объема
+    pub fn reset_peak(&self) {
+        self.peak.store(self.current(), Ordering::Relaxed);
+    }
+
+    fn add(&self, size: usize) {
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn sub(&self, size: usize) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            self.add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.sub(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            self.add(new_size);
+            self.sub(layout.size());
+        }
+        new_ptr
+    }
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_alloc_test() {
+        let allocator = PeakAlloc::new();
+        let layout = Layout::array::<u64>(1000).unwrap();
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            assert_eq!(8000, allocator.current());
+            assert_eq!(8000, allocator.peak());
+
+            let ptr = allocator.realloc(ptr, layout, 16_000);
+            assert!(!ptr.is_null());
+            assert_eq!(16_000, allocator.current());
+            assert_eq!(24_000, allocator.peak());
+
+            allocator.dealloc(ptr, Layout::array::<u64>(2000).unwrap());
+        }
+        assert_eq!(0, allocator.current());
+        assert_eq!(24_000, allocator.peak());
+
+        allocator.reset_peak();
+        assert_eq!(0, allocator.peak());
+    }
+}
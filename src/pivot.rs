@@ -0,0 +1,145 @@
+use crate::utils::Random;
+use std::cmp::Ordering;
+use std::sync::Mutex;
+
+/// Стратегия выбора опорного элемента для быстрой сортировки
+pub trait PivotSelector<T> {
+    /// Вернуть индекс опорного элемента в непустом слайсе
+    fn select(&self, arr: &[T]) -> usize;
+}
+
+/// Последний элемент слайса. На отсортированных данных приводит
+/// к квадратичному времени работы.
+#[allow(dead_code)]
+pub struct LastElement;
+
+impl<T> PivotSelector<T> for LastElement {
+    fn select(&self, arr: &[T]) -> usize {
+        arr.len() - 1
+    }
+}
+
+/// Случайный элемент слайса
+#[allow(dead_code)]
+pub struct RandomPivot {
+    random: Mutex<Random>,
+}
+
+impl RandomPivot {
+    #[allow(dead_code)]
+    pub fn new(seed: u32) -> Self {
+        Self {
+            random: Mutex::new(Random::new(seed)),
+        }
+    }
+}
+
+impl<T> PivotSelector<T> for RandomPivot {
+    fn select(&self, arr: &[T]) -> usize {
+        self.random.lock().unwrap().next() as usize % arr.len()
+    }
+}
+
+/// Медиана первого, среднего и последнего элементов слайса.
+/// На отсортированных данных выбирает точную медиану.
+/// Именно так выбирают опорный элемент основные реализации.
+#[allow(dead_code)]
+pub struct MedianOfThree;
+
+impl<T: Ord> PivotSelector<T> for MedianOfThree {
+    fn select(&self, arr: &[T]) -> usize {
+        median_of_three_by(arr, T::cmp)
+    }
+}
+
+/// Индекс медианы первого, среднего и последнего элементов непустого слайса
+/// по заданному сравнению (см. [MedianOfThree]).
+pub fn median_of_three_by<T>(arr: &[T], compare: impl Fn(&T, &T) -> Ordering) -> usize {
+    let le = |i: usize, j: usize| compare(&arr[i], &arr[j]) != Ordering::Greater;
+    let (a, b, c) = (0, arr.len() / 2, arr.len() - 1);
+    if le(a, b) == le(b, c) {
+        b
+    } else if le(b, a) == le(a, c) {
+        a
+    } else {
+        c
+    }
+}
+
+/// Медиана медиан: гарантирует, что по обе стороны от опорного элемента
+/// окажется не меньше 30% элементов. Выбор k-й статистики с таким опорным элементом
+/// работает за O(n) в худшем случае, но с заметно большей константой.
+#[allow(dead_code)]
+pub struct MedianOfMedians;
+
+impl<T: Ord + Copy> PivotSelector<T> for MedianOfMedians {
+    fn select(&self, arr: &[T]) -> usize {
+        let pivot = median_of_medians(arr);
+        arr.iter().position(|x| *x == pivot).unwrap()
+    }
+}
+
+/// Найти медиану медиан групп по 5 элементов. O(n) в худшем случае.
+#[allow(dead_code)]
+pub fn median_of_medians<T: Ord + Copy>(arr: &[T]) -> T {
+    let medians: Vec<T> = arr.chunks(5).map(|group| median_of_small(group)).collect();
+    linear_select(&medians, (medians.len() - 1) / 2)
+}
+
+/// Найти k-ю порядковую статистику, используя медиану медиан в качестве опорного элемента.
+/// O(n) в худшем случае.
+fn linear_select<T: Ord + Copy>(arr: &[T], k: usize) -> T {
+    if arr.len() <= 5 {
+        let mut arr = arr.to_vec();
+        arr.sort();
+        return arr[k];
+    }
+
+    let pivot = median_of_medians(arr);
+    let less: Vec<T> = arr.iter().copied().filter(|x| *x < pivot).collect();
+    let eq_count = arr.iter().filter(|x| **x == pivot).count();
+
+    if k < less.len() {
+        linear_select(&less, k)
+    } else if k < less.len() + eq_count {
+        pivot
+    } else {
+        let greater: Vec<T> = arr.iter().copied().filter(|x| *x > pivot).collect();
+        linear_select(&greater, k - less.len() - eq_count)
+    }
+}
+
+fn median_of_small<T: Ord + Copy>(group: &[T]) -> T {
+    let mut group = group.to_vec();
+    group.sort();
+    group[(group.len() - 1) / 2]
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_medians_test() {
+        let mut random = Random::new(3);
+        for arr_len in [1, 5, 10, 1000, 100_000] {
+            let arr = random.next_vec(arr_len);
+            let mut sorted_arr = arr.clone();
+            sorted_arr.sort();
+
+            // Медиана медиан лежит между 30-м и 70-м процентилями
+            let median = median_of_medians(&arr);
+            let rank = sorted_arr.partition_point(|&x| x < median);
+            assert!(rank <= arr_len * 7 / 10);
+            assert!(rank + 1 >= arr_len * 3 / 10);
+
+            for k in [0, arr_len / 2, arr_len - 1] {
+                assert_eq!(sorted_arr[k], linear_select(&arr, k));
+            }
+        }
+    }
+}
@@ -1,20 +1,151 @@
-use crate::parallel_primitives::{blocked_for, par_filter};
+#[cfg(feature = "handwritten")]
+use crate::parallel_primitives::{
+    blocked_for, blocked_for_dyn, is_single_threaded, par_copy, par_filter, par_filter_cloned,
+    par_for_range, par_gather, par_inclusive_scan, par_inline_prefix_sums, par_is_sorted, par_map,
+    par_map_into, par_map_uninit, par_merge, par_partition3, par_scatter, par_tabulate,
+};
+use crate::pivot::{median_of_three_by, MedianOfThree, PivotSelector};
+#[cfg(feature = "handwritten")]
+use crate::utils::UnsafeSlice;
 use rayon::prelude::*;
+use std::cell::Cell;
+use std::cmp;
+#[cfg(feature = "handwritten")]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
+mod algorithm;
+
+#[allow(unused_imports)]
+pub use algorithm::{
+    parallel_sort, sort_with, SortAlgorithm, UnknownSortAlgorithm, SEQUENTIAL_MAX_LEN,
+    SIMPLE_PARALLEL_MAX_LEN,
+};
+
+/// Последовательная быстрая сортировка. Опорный элемент - медиана трех (см. [MedianOfThree]),
+/// разбиение трехпутевое, поэтому ни упорядоченные, ни одинаковые данные
+/// не дают линейной глубины рекурсии.
 pub fn sequential_quicksort<T: Ord>(arr: &mut [T]) {
-    if arr.is_empty() {
+    sequential_quicksort_by(arr, T::cmp);
+}
+
+/// Аналог [sequential_quicksort] с заданным сравнением.
+fn sequential_quicksort_by<T>(arr: &mut [T], compare: impl Fn(&T, &T) -> cmp::Ordering + Copy) {
+    if arr.len() <= SORT_NETWORK_MAX_LEN {
+        sort_small_by(arr, compare);
+        return;
+    }
+    let last = arr.len() - 1;
+    arr.swap(median_of_three_by(arr, compare), last);
+    let (eq_start, eq_end) = partition3_two_pass_by(arr, compare);
+    sequential_quicksort_by(&mut arr[..eq_start], compare);
+    sequential_quicksort_by(&mut arr[eq_end..], compare);
+}
+
+/// Трёхпутевое разбиение по последнему элементу: `[0, eq_start)` меньше опорного,
+/// `[eq_start, eq_end)` равны ему, `[eq_end, len)` больше.
+/// После обычного двухпутевого разбиения ([partition_by]) равные опорному элементы
+/// дополнительно собираются сразу за ним, поэтому на массиве из одинаковых элементов
+/// глубина рекурсии не становится линейной, а на различных элементах порядок
+/// получается тем же, что и у [partition_by].
+fn partition3<T: Ord>(arr: &mut [T]) -> (usize, usize) {
+    partition3_two_pass_by(arr, T::cmp)
+}
+
+/// Аналог [partition3] с заданным сравнением.
+fn partition3_two_pass_by<T>(
+    arr: &mut [T],
+    compare: impl Fn(&T, &T) -> cmp::Ordering,
+) -> (usize, usize) {
+    let eq_start = partition_by(arr, |a, b| compare(a, b) == cmp::Ordering::Less);
+    let (pivot, greater_or_eq) = arr[eq_start..].split_first_mut().unwrap();
+    let mut eq_count = 0;
+    for i in 0..greater_or_eq.len() {
+        if compare(&greater_or_eq[i], pivot) == cmp::Ordering::Equal {
+            greater_or_eq.swap(i, eq_count);
+            eq_count += 1;
+        }
+    }
+    (eq_start, eq_start + 1 + eq_count)
+}
+
+/// Последовательная быстрая сортировка с трехпутевым разбиением за один проход
+/// (см. [partition3_one_pass]): каждый элемент сравнивается с опорным один раз,
+/// а равные опорному сразу исключаются из рекурсии. Поэтому на данных с k различными
+/// значениями глубина рекурсии не превосходит k. Опорный элемент - медиана трех.
+/// Используется как базовый случай параллельных сортировок.
+pub fn sequential_quicksort_3way<T: Ord>(arr: &mut [T]) {
+    if arr.len() <= SORT_NETWORK_MAX_LEN {
+        sort_small(arr);
+        return;
+    }
+    let last = arr.len() - 1;
+    arr.swap(MedianOfThree.select(arr), last);
+    let (eq_start, eq_end) = partition3_one_pass(arr);
+    sequential_quicksort_3way(&mut arr[..eq_start]);
+    sequential_quicksort_3way(&mut arr[eq_end..]);
+}
+
+/// Трехпутевое разбиение непустого слайса по последнему элементу за один проход,
+/// результат такой же, как у [partition3]. В отличие от него, порядок элементов,
+/// больших опорного, не сохраняется даже на различных элементах.
+///
+/// Меньшие и равные опорному элементы переносятся вперед, а большие остаются на месте
+/// (в отличие от голландского флага Дейкстры, который переворачивает часть больших).
+/// Поэтому упорядоченный массив остается упорядоченным в обеих частях,
+/// и медиана трех на следующих уровнях снова делит их пополам.
+fn partition3_one_pass<T: Ord>(arr: &mut [T]) -> (usize, usize) {
+    partition3_by(arr, T::cmp)
+}
+
+/// Аналог [partition3_one_pass] с заданным сравнением.
+fn partition3_by<T>(arr: &mut [T], compare: impl Fn(&T, &T) -> cmp::Ordering) -> (usize, usize) {
+    let last = arr.len() - 1;
+    // [0, lt) - меньше опорного, [lt, eq) - равны ему, [eq, i) - больше.
+    // Опорный элемент остается на последнем месте до конца прохода
+    let (mut lt, mut eq) = (0, 0);
+    for i in 0..last {
+        match compare(&arr[i], &arr[last]) {
+            cmp::Ordering::Less => {
+                arr.swap(i, eq);
+                arr.swap(eq, lt);
+                lt += 1;
+                eq += 1;
+            }
+            cmp::Ordering::Equal => {
+                arr.swap(i, eq);
+                eq += 1;
+            }
+            cmp::Ordering::Greater => {}
+        }
+    }
+    arr.swap(eq, last);
+    (lt, eq + 1)
+}
+
+/// Аналог [sequential_quicksort_3way] с заданным сравнением.
+/// Опорный элемент - медиана трех (см. [median_of_three_by]),
+/// поэтому упорядоченные данные не дают квадратичного времени.
+fn sequential_quicksort_3way_by<T>(
+    arr: &mut [T],
+    compare: impl Fn(&T, &T) -> cmp::Ordering + Copy,
+) {
+    if arr.len() <= 1 {
         return;
     }
-    let middle = partition(arr);
-    let (left, right) = arr.split_at_mut(middle);
-    sequential_quicksort(left);
-    sequential_quicksort(&mut right[1..]);
+    let last = arr.len() - 1;
+    arr.swap(median_of_three_by(arr, compare), last);
+    let (eq_start, eq_end) = partition3_by(arr, compare);
+    sequential_quicksort_3way_by(&mut arr[..eq_start], compare);
+    sequential_quicksort_3way_by(&mut arr[eq_end..], compare);
 }
 
-fn partition<T: Ord>(arr: &mut [T]) -> usize {
+/// Разбиение по последнему элементу с заданным отношением "меньше".
+fn partition_by<T>(arr: &mut [T], is_less: impl Fn(&T, &T) -> bool) -> usize {
     let mut m = 0;
     for i in 0..arr.len() {
-        if arr[i] < arr[arr.len() - 1] {
+        if is_less(&arr[i], &arr[arr.len() - 1]) {
             arr.swap(i, m);
             m += 1;
         }
@@ -23,206 +154,2556 @@ fn partition<T: Ord>(arr: &mut [T]) -> usize {
     m
 }
 
+/// Отсортировать массив через [sequential_quicksort] и вернуть количество сравнений элементов,
+/// включая выбор медианы трех и сортирующие сети.
+#[allow(dead_code)]
+pub fn sequential_quicksort_counted<T: Ord>(arr: &mut [T]) -> u64 {
+    let comparisons = Cell::new(0);
+    sequential_quicksort_by(arr, |a, b| {
+        comparisons.set(comparisons.get() + 1);
+        a.cmp(b)
+    });
+    comparisons.get()
+}
+
+/// Максимальная длина массива, для которой есть сортирующая сеть.
+const SORT_NETWORK_MAX_LEN: usize = 16;
+/// Количество компараторов в сети Бэтчера на 16 входов, в сетях меньшего размера их меньше.
+const SORT_NETWORK_MAX_COMPARATORS: usize = 63;
+
+/// Отсортировать массив длины `N <= 16` сортирующей сетью: фиксированной последовательностью
+/// сравнений с обменом, не зависящей от данных. В отличие от быстрой сортировки нет ни рекурсии,
+/// ни непредсказуемых переходов по результатам разбиения.
+///
+/// Используется сеть четно-нечетного слияния Бэтчера. Для `N <= 8` она оптимальна
+/// по числу компараторов, для больших `N` длиннее лучших известных сетей на 2-3 компаратора.
+/// При `N > 16` код не компилируется.
+#[allow(dead_code)]
+pub fn sort_network<T: Ord, const N: usize>(arr: &mut [T; N]) {
+    sort_network_by(arr, T::cmp);
+}
+
+/// Аналог [sort_network] с заданным сравнением.
+fn sort_network_by<T, const N: usize>(arr: &mut [T; N], compare: impl Fn(&T, &T) -> cmp::Ordering) {
+    let (comparators, count) = &SortNetwork::<N>::COMPARATORS;
+    for &(i, j) in &comparators[..*count] {
+        if compare(&arr[j], &arr[i]) == cmp::Ordering::Less {
+            arr.swap(i, j);
+        }
+    }
+}
+
+struct SortNetwork<const N: usize>;
+
+impl<const N: usize> SortNetwork<N> {
+    /// Компараторы `(i, j)`, `i < j`, и их количество
+    const COMPARATORS: ([(usize, usize); SORT_NETWORK_MAX_COMPARATORS], usize) = batcher_network(N);
+}
+
+/// Построить сеть четно-нечетного слияния Бэтчера на `n` входов.
+/// Для `n`, не являющегося степенью двойки, из сети на ближайшую большую степень двойки
+/// выбрасываются компараторы, задевающие несуществующие входы.
+const fn batcher_network(n: usize) -> ([(usize, usize); SORT_NETWORK_MAX_COMPARATORS], usize) {
+    assert!(n <= SORT_NETWORK_MAX_LEN);
+    let mut comparators = [(0, 0); SORT_NETWORK_MAX_COMPARATORS];
+    let mut count = 0;
+    // Сливаются соседние отсортированные группы по p элементов,
+    // k - расстояние между сравниваемыми элементами на текущем шаге слияния
+    let mut p = 1;
+    while p < n {
+        let mut k = p;
+        while k >= 1 {
+            let mut j = k % p;
+            while j + k < n {
+                let mut i = 0;
+                while i < k && i + j + k < n {
+                    if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                        comparators[count] = (i + j, i + j + k);
+                        count += 1;
+                    }
+                    i += 1;
+                }
+                j += 2 * k;
+            }
+            k /= 2;
+        }
+        p *= 2;
+    }
+    (comparators, count)
+}
+
+/// Отсортировать массив длины не больше [SORT_NETWORK_MAX_LEN] подходящей сетью.
+fn sort_small<T: Ord>(arr: &mut [T]) {
+    sort_small_by(arr, T::cmp);
+}
+
+/// Аналог [sort_small] с заданным сравнением.
+fn sort_small_by<T>(arr: &mut [T], compare: impl Fn(&T, &T) -> cmp::Ordering) {
+    match arr.len() {
+        0 | 1 => {}
+        2 => sort_network_by::<_, 2>(arr.try_into().unwrap(), compare),
+        3 => sort_network_by::<_, 3>(arr.try_into().unwrap(), compare),
+        4 => sort_network_by::<_, 4>(arr.try_into().unwrap(), compare),
+        5 => sort_network_by::<_, 5>(arr.try_into().unwrap(), compare),
+        6 => sort_network_by::<_, 6>(arr.try_into().unwrap(), compare),
+        7 => sort_network_by::<_, 7>(arr.try_into().unwrap(), compare),
+        8 => sort_network_by::<_, 8>(arr.try_into().unwrap(), compare),
+        9 => sort_network_by::<_, 9>(arr.try_into().unwrap(), compare),
+        10 => sort_network_by::<_, 10>(arr.try_into().unwrap(), compare),
+        11 => sort_network_by::<_, 11>(arr.try_into().unwrap(), compare),
+        12 => sort_network_by::<_, 12>(arr.try_into().unwrap(), compare),
+        13 => sort_network_by::<_, 13>(arr.try_into().unwrap(), compare),
+        14 => sort_network_by::<_, 14>(arr.try_into().unwrap(), compare),
+        15 => sort_network_by::<_, 15>(arr.try_into().unwrap(), compare),
+        16 => sort_network_by::<_, 16>(arr.try_into().unwrap(), compare),
+        _ => unreachable!(),
+    }
+}
+
+/// Сортировка вставками. O(n^2), но на очень маленьких массивах быстрее быстрой сортировки.
+#[allow(dead_code)]
+pub fn insertion_sort<T: Ord>(arr: &mut [T]) {
+    for i in 1..arr.len() {
+        let mut j = i;
+        while j > 0 && arr[j] < arr[j - 1] {
+            arr.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+/// Последовательная быстрая сортировка с заданной стратегией выбора опорного элемента.
+#[allow(dead_code)]
+pub fn sequential_quicksort_with_pivot<T: Ord>(arr: &mut [T], pivot: &impl PivotSelector<T>) {
+    if arr.is_empty() {
+        return;
+    }
+    let last = arr.len() - 1;
+    arr.swap(pivot.select(arr), last);
+    let (eq_start, eq_end) = partition3(arr);
+    sequential_quicksort_with_pivot(&mut arr[..eq_start], pivot);
+    sequential_quicksort_with_pivot(&mut arr[eq_end..], pivot);
+}
+
 /// Максимально простая параллельная реализация быстрой сортировки,
 /// обладающая work-ом последовательной реализации
 /// (с точностью до константы, если исключить накладные расходы fork-join)
-/// и O(n log n) span-ом.
+/// и O(n log n) span-ом. Как и в [sequential_quicksort], опорный элемент - медиана трех,
+/// а разбиение трехпутевое.
 pub fn simple_parallel_quicksort<T: Ord + Send>(arr: &mut [T]) {
     if arr.len() <= 1024 {
-        sequential_quicksort(arr);
+        sequential_quicksort_3way(arr);
         return;
     }
 
-    let middle = partition(arr);
-    let (left, right) = arr.split_at_mut(middle);
+    let last = arr.len() - 1;
+    arr.swap(MedianOfThree.select(arr), last);
+    let (eq_start, eq_end) = partition3_one_pass(arr);
+    let (left, right) = arr.split_at_mut(eq_start);
     rayon::join(
         || simple_parallel_quicksort(left),
-        || simple_parallel_quicksort(&mut right[1..]),
+        || simple_parallel_quicksort(&mut right[eq_end - eq_start..]),
+    );
+}
+
+/// Аналог [simple_parallel_quicksort] с заданной стратегией выбора опорного элемента.
+#[allow(dead_code)]
+pub fn simple_parallel_quicksort_with_pivot<T: Ord + Send>(
+    arr: &mut [T],
+    pivot: &(impl PivotSelector<T> + Sync),
+) {
+    if arr.len() <= 1024 {
+        sequential_quicksort_with_pivot(arr, pivot);
+        return;
+    }
+
+    let last = arr.len() - 1;
+    arr.swap(pivot.select(arr), last);
+    let (eq_start, eq_end) = partition3_one_pass(arr);
+    let (left, right) = arr.split_at_mut(eq_start);
+    rayon::join(
+        || simple_parallel_quicksort_with_pivot(left, pivot),
+        || simple_parallel_quicksort_with_pivot(&mut right[eq_end - eq_start..], pivot),
+    );
+}
+
+/// Аналог [simple_parallel_quicksort] с заданным сравнением.
+/// Разбиение трехпутевое, поэтому элементы с равными ключами не уходят в рекурсию.
+fn simple_parallel_quicksort_by<T: Send>(
+    arr: &mut [T],
+    compare: impl Fn(&T, &T) -> cmp::Ordering + Copy + Sync,
+) {
+    if arr.len() <= 1024 {
+        sequential_quicksort_3way_by(arr, compare);
+        return;
+    }
+
+    let last = arr.len() - 1;
+    arr.swap(median_of_three_by(arr, compare), last);
+    let (eq_start, eq_end) = partition3_by(arr, compare);
+    let (left, right) = arr.split_at_mut(eq_start);
+    rayon::join(
+        || simple_parallel_quicksort_by(left, compare),
+        || simple_parallel_quicksort_by(&mut right[eq_end - eq_start..], compare),
+    );
+}
+
+/// Параллельная быстрая сортировка по ключу (на основе [simple_parallel_quicksort]).
+///
+/// Ключ вычисляется заново при каждом сравнении, то есть O(n log n) раз,
+/// зато не требуется дополнительная память.
+/// Если ключ дорого вычислять, лучше подойдет [parallel_quicksort_by_cached_key].
+#[allow(dead_code)]
+pub fn parallel_quicksort_by_key<T: Send, K: Ord>(
+    arr: &mut [T],
+    key: impl Fn(&T) -> K + Copy + Sync,
+) {
+    simple_parallel_quicksort_by(arr, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Параллельная быстрая сортировка с компаратором, который может вернуть ошибку
+/// (например, если ключ сравнения нужно распарсить).
+///
+/// После первой ошибки выставляется флаг, и задачи перестают разбивать свои части.
+/// Если ошибки возникли сразу в нескольких задачах, возвращается одна из них (какая - не определено),
+/// остальные отбрасываются. После ошибки массив содержит те же элементы в неопределенном порядке.
+#[allow(dead_code)]
+pub fn parallel_quicksort_try_by<T: Send, E: Send>(
+    arr: &mut [T],
+    compare: impl Fn(&T, &T) -> Result<cmp::Ordering, E> + Copy + Sync,
+) -> Result<(), E> {
+    let failed = AtomicBool::new(false);
+    let error = Mutex::new(None);
+    let compare = |a: &T, b: &T| match compare(a, b) {
+        Ok(ordering) => ordering,
+        Err(e) => {
+            error.lock().unwrap().get_or_insert(e);
+            failed.store(true, Ordering::Relaxed);
+            cmp::Ordering::Equal
+        }
+    };
+    parallel_quicksort_try_by_helper(arr, compare, &failed);
+    match error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn parallel_quicksort_try_by_helper<T: Send>(
+    arr: &mut [T],
+    compare: impl Fn(&T, &T) -> cmp::Ordering + Copy + Sync,
+    failed: &AtomicBool,
+) {
+    if arr.len() <= 1 || failed.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let len = arr.len();
+    let last = len - 1;
+    arr.swap(median_of_three_by(arr, compare), last);
+    let (eq_start, eq_end) = partition3_by(arr, compare);
+    let (left, right) = arr.split_at_mut(eq_start);
+    let right = &mut right[eq_end - eq_start..];
+    if len <= 1024 {
+        parallel_quicksort_try_by_helper(left, compare, failed);
+        parallel_quicksort_try_by_helper(right, compare, failed);
+    } else {
+        rayon::join(
+            || parallel_quicksort_try_by_helper(left, compare, failed),
+            || parallel_quicksort_try_by_helper(right, compare, failed),
+        );
+    }
+}
+
+/// Параллельно отсортировать строки таблицы по значению в столбце `col`
+/// (через [parallel_quicksort_by_key]). Строки переставляются целиком.
+#[allow(dead_code)]
+pub fn parallel_sort_rows<const N: usize>(rows: &mut [[i64; N]], col: usize) {
+    assert!(col < N);
+    parallel_quicksort_by_key(rows, |row| row[col]);
+}
+
+/// Порядок сортировки.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+/// Параллельная быстрая сортировка (на основе [simple_parallel_quicksort])
+/// по возрастанию или по убыванию.
+/// Для убывания при разбиении просто переворачивается сравнение.
+#[allow(dead_code)]
+pub fn parallel_quicksort_ordered<T: Ord + Send>(arr: &mut [T], order: SortOrder) {
+    match order {
+        SortOrder::Ascending => simple_parallel_quicksort_by(arr, |a, b| a.cmp(b)),
+        SortOrder::Descending => simple_parallel_quicksort_by(arr, |a, b| b.cmp(a)),
+    }
+}
+
+/// Параллельная быстрая сортировка по ключу, вычисляющая ключ ровно один раз
+/// для каждого элемента (аналог [slice::sort_by_cached_key]).
+///
+/// Ключи заранее считаются через [par_map], после чего сортируются индексы элементов,
+/// а затем элементы переставляются в соответствии с ними.
+/// Требуется O(n) дополнительной памяти под ключи, индексы и копию массива,
+/// поэтому для дешевых ключей [parallel_quicksort_by_key] быстрее.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_by_cached_key<
+    T: Default + Copy + Send + Sync,
+    K: Ord + Default + Clone + Send + Sync,
+>(
+    arr: &mut [T],
+    key: impl Fn(&T) -> K + Copy + Sync,
+) {
+    let keys = par_map(arr, key);
+    let mut indices: Vec<usize> = (0..arr.len()).collect();
+    simple_parallel_quicksort_by(&mut indices, |&a, &b| keys[a].cmp(&keys[b]));
+    let sorted = par_map(&indices, |&i| arr[i]);
+    arr.copy_from_slice(&sorted);
+}
+
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_seq_memcpy],
+/// с минимальными требованиями к типу элементов.
+///
+/// Остальные реализации с полилогарифмическим span-ом требуют `Default`, чтобы заранее
+/// заполнить массивы-результаты фильтрации, и `Copy`, чтобы копировать элементы через memcpy.
+/// Здесь же используется [par_filter_cloned], записывающий клоны элементов
+/// в неинициализированную память, поэтому достаточно `Clone`.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_cloned<T: Ord + Clone + Send + Sync>(arr: &mut [T]) {
+    if arr.len() <= 4096 {
+        sequential_quicksort_3way(arr);
+        return;
+    }
+
+    let pivot = arr[median_of_three_by(arr, T::cmp)].clone();
+
+    parallel_quicksort_helper(
+        arr,
+        |arr| {
+            (
+                par_filter_cloned(arr, |x| *x < pivot),
+                par_filter_cloned(arr, |x| *x == pivot),
+                par_filter_cloned(arr, |x| *x > pivot),
+            )
+        },
+        |arr, less, eq, greater| {
+            arr[0..less.len()].clone_from_slice(less);
+            arr[less.len()..less.len() + eq.len()].clone_from_slice(eq);
+            arr[less.len() + eq.len()..].clone_from_slice(greater);
+        },
+        |part, _| parallel_quicksort_cloned(part),
     );
 }
 
 /// Параллельная быстрая сортировка с O(polylog n) span (за исключением копирования).
 /// В текущей реализации span = O(log^3 n),
-/// но можно получить и O(log^2 n), если изменить [par_inline_prefix_sums]
-/// в соответствии с описанным там способом.
+/// но можно получить и O(log^2 n), если включить feature `log-span-scan`
+/// (см. [par_inline_prefix_sums]).
 ///
 /// Используются самописные параллельные примитивы.
 ///
 /// Для конкатенации массивов используется последовательный memcpy
 /// (при расчете span-а он считается за O(1))
+#[cfg(feature = "handwritten")]
 pub fn parallel_quicksort_seq_memcpy<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) {
-    if arr.len() <= 4096 {
-        sequential_quicksort(arr);
+    if arr.len() <= 4096 || is_single_threaded() {
+        sequential_quicksort_3way(arr);
         return;
     }
 
-    let (less, eq, greater) = parallel_quicksort_helper(arr);
-
-    arr[0..less.len()].copy_from_slice(&less);
-    arr[less.len()..less.len() + eq.len()].copy_from_slice(&eq);
-    arr[less.len() + eq.len()..].copy_from_slice(&greater);
+    parallel_quicksort_helper(arr, parallel_partition, copy_parts, |part, _| {
+        parallel_quicksort_seq_memcpy(part)
+    });
 }
 
 /// Параллельная быстрая сортировка с O(polylog n) span (за исключением копирования).
 /// В текущей реализации span = O(log^3 n),
-/// но можно получить и O(log^2 n), если изменить [par_inline_prefix_sums]
-/// в соответствии с описанным там способом.
+/// но можно получить и O(log^2 n), если включить feature `log-span-scan`
+/// (см. [par_inline_prefix_sums]).
 ///
 /// Используются самописные параллельные примитивы.
 ///
 /// Для конкатенации массивов используется последовательный memcpy
 /// (при расчете span-а он считается за O(1)),
 /// запущенный параллельно в трех копиях.
+///
+/// В пуле rayon из одного потока сразу сортирует последовательно
+/// (см. [is_single_threaded]).
+#[cfg(feature = "handwritten")]
 pub fn parallel_quicksort_3par_memcpy<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) {
-    if arr.len() <= 4096 {
-        sequential_quicksort(arr);
+    if arr.len() <= 4096 || is_single_threaded() {
+        sequential_quicksort_3way(arr);
         return;
     }
 
-    let (less, eq, greater) = parallel_quicksort_helper(arr);
-
-    let (src_less, src_ge) = arr.split_at_mut(less.len());
-    let (src_eq, src_greater) = src_ge.split_at_mut(eq.len());
+    parallel_quicksort_helper(
+        arr,
+        parallel_partition,
+        |arr, less, eq, greater| {
+            let (src_less, src_ge) = arr.split_at_mut(less.len());
+            let (src_eq, src_greater) = src_ge.split_at_mut(eq.len());
 
-    rayon::join(
-        || {
             rayon::join(
-                || src_less.copy_from_slice(&less),
-                || src_eq.copy_from_slice(&eq),
-            )
+                || {
+                    rayon::join(
+                        || src_less.copy_from_slice(less),
+                        || src_eq.copy_from_slice(eq),
+                    )
+                },
+                || src_greater.copy_from_slice(greater),
+            );
         },
-        || src_greater.copy_from_slice(&greater),
+        |part, _| parallel_quicksort_seq_memcpy(part),
     );
 }
 
 /// Параллельная быстрая сортировка с O(polylog n) span.
 /// В текущей реализации span = O(log^3 n),
-/// но можно получить и O(log^2 n), если изменить [par_inline_prefix_sums]
-/// в соответствии с описанным там способом.
+/// но можно получить и O(log^2 n), если включить feature `log-span-scan`
+/// (см. [par_inline_prefix_sums]).
 ///
 /// Используются самописные параллельные примитивы.
 ///
 /// Для конкатенации массивов используется memcpy, запущенный параллельно через blocked_for.
 /// Поэтому данная реализация имеет поистине полилогарифмический span.
+///
+/// Перед сортировкой массив проверяется на упорядоченность через [par_is_sorted]:
+/// на уже отсортированных данных сортировка завершается сразу, без единого разбиения.
+/// На случайных данных проверка прерывается на первых же элементах и почти ничего не стоит.
+#[cfg(feature = "handwritten")]
 pub fn parallel_quicksort_par_memcpy<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) {
     if arr.len() <= 4096 {
-        sequential_quicksort(arr);
+        sequential_quicksort_3way(arr);
+        return;
+    }
+    if par_is_sorted(arr) {
+        return;
+    }
+    if is_single_threaded() {
+        sequential_quicksort_3way(arr);
         return;
     }
 
-    let (less, eq, greater) = parallel_quicksort_helper(arr);
+    parallel_quicksort_helper(arr, parallel_partition, par_copy_parts, |part, _| {
+        parallel_quicksort_seq_memcpy(part)
+    });
+}
+
+/// Общий уровень рекурсии параллельных быстрых сортировок.
+///
+/// Разбить `arr` на меньшие, равные и большие опорного через `partition`,
+/// сразу вернуть части в `arr` через `copy_back` и только затем параллельно отсортировать
+/// меньшую и большую части на месте через `recurse`. Вторым аргументом `recurse` получает
+/// смещение части в `arr`, результаты для обеих частей возвращаются парой.
+/// Всё, что вариант делает на каждом уровне (подсчет статистики, события и т.д.),
+/// удобно делать в `copy_back`: там уже известны все три части.
+///
+/// Временные векторы освобождаются до рекурсии, поэтому дополнительная память
+/// не накапливается по уровням, а в каждый момент составляет O(n).
+fn parallel_quicksort_helper<T: Send, R: Send>(
+    arr: &mut [T],
+    partition: impl FnOnce(&[T]) -> (Vec<T>, Vec<T>, Vec<T>),
+    copy_back: impl FnOnce(&mut [T], &[T], &[T], &[T]),
+    recurse: impl Fn(&mut [T], usize) -> R + Sync,
+) -> (R, R) {
+    let (less_len, eq_len) = {
+        let (less, eq, greater) = partition(arr);
+        copy_back(arr, &less, &eq, &greater);
+        (less.len(), eq.len())
+    };
+
+    let (arr_less, arr_ge) = arr.split_at_mut(less_len);
+    let arr_greater = &mut arr_ge[eq_len..];
+    rayon::join(
+        || recurse(arr_less, 0),
+        || recurse(arr_greater, less_len + eq_len),
+    )
+}
 
-    let (src_less, src_ge) = arr.split_at_mut(less.len());
-    let (src_eq, src_greater) = src_ge.split_at_mut(eq.len());
+/// Последовательно скопировать части разбиения обратно в `arr` (см. [parallel_quicksort_helper]).
+fn copy_parts<T: Copy>(arr: &mut [T], less: &[T], eq: &[T], greater: &[T]) {
+    arr[0..less.len()].copy_from_slice(less);
+    arr[less.len()..less.len() + eq.len()].copy_from_slice(eq);
+    arr[less.len() + eq.len()..].copy_from_slice(greater);
+}
 
+/// Аналог [copy_parts], копирующий части параллельно через [par_copy].
+#[cfg(feature = "handwritten")]
+fn par_copy_parts<T: Copy + Send + Sync>(arr: &mut [T], less: &[T], eq: &[T], greater: &[T]) {
+    let (arr_less, arr_ge) = arr.split_at_mut(less.len());
+    let (arr_eq, arr_greater) = arr_ge.split_at_mut(eq.len());
     rayon::join(
-        || rayon::join(|| par_copy(src_less, &less), || par_copy(src_eq, &eq)),
-        || par_copy(src_greater, &greater),
+        || rayon::join(|| par_copy(arr_less, less), || par_copy(arr_eq, eq)),
+        || par_copy(arr_greater, greater),
     );
 }
 
-fn par_copy<T: Copy + Send + Sync>(dst: &mut [T], src: &[T]) {
-    assert_eq!(dst.len(), src.len());
-    const COPY_BLOCK: usize = 4096;
-    blocked_for::<_, COPY_BLOCK>(dst, |block_index, dst_block| {
-        let from = COPY_BLOCK * block_index;
-        dst_block.copy_from_slice(&src[from..from + dst_block.len()]);
-    });
+/// Отсортировать вектор через [parallel_quicksort_par_memcpy]
+/// и освободить лишнюю емкость (см. [Vec::shrink_to_fit]).
+/// Удобно для больших временных векторов, выделенных с запасом.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_compact<T: Ord + Default + Copy + Send + Sync>(v: &mut Vec<T>) {
+    parallel_quicksort_par_memcpy(v);
+    v.shrink_to_fit();
 }
 
-fn parallel_quicksort_helper<T: Ord + Default + Copy + Send + Sync>(
-    arr: &[T],
-) -> (Vec<T>, Vec<T>, Vec<T>) {
-    let pivot = arr.last().unwrap();
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_par_memcpy],
+/// которая не изменяет `src`, а записывает результат в `dst` той же длины.
+/// Части разбиения сортируются рекурсивно сразу в соответствующие части `dst`,
+/// поэтому обратного копирования в исходный массив нет.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_out<T: Ord + Default + Copy + Send + Sync>(src: &[T], dst: &mut [T]) {
+    assert_eq!(src.len(), dst.len());
+    if src.len() <= 4096 {
+        dst.copy_from_slice(src);
+        sequential_quicksort_3way(dst);
+        return;
+    }
 
-    let mut less = par_filter(arr, |x| x < pivot);
-    let eq = par_filter(arr, |x| x == pivot);
-    let mut greater = par_filter(arr, |x| x > pivot);
+    let (less, eq, greater) = parallel_partition(src);
 
+    let (dst_less, dst_ge) = dst.split_at_mut(less.len());
+    let (dst_eq, dst_greater) = dst_ge.split_at_mut(eq.len());
     rayon::join(
-        || parallel_quicksort_seq_memcpy(&mut less),
-        || parallel_quicksort_seq_memcpy(&mut greater),
+        || {
+            rayon::join(
+                || parallel_quicksort_out(&less, dst_less),
+                || par_copy(dst_eq, &eq),
+            )
+        },
+        || parallel_quicksort_out(&greater, dst_greater),
+    );
+}
+
+/// Параллельная быстрая сортировка пар `(ключ, значение)` по ключу,
+/// устроенная так же, как [parallel_quicksort_par_memcpy].
+/// Разбиение сравнивает только ключи, а значения просто переносятся вместе с ними,
+/// поэтому от значений требуется лишь `Copy`.
+/// Опорный ключ - медиана трех, поэтому уже упорядоченные ключи (частый случай
+/// для [parallel_argsort]) не дают линейной глубины рекурсии.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_pairs<K: Ord + Copy + Send + Sync, V: Copy + Send + Sync>(
+    arr: &mut [(K, V)],
+) {
+    if arr.len() <= 4096 {
+        sequential_quicksort_3way_by(arr, |a, b| a.0.cmp(&b.0));
+        return;
+    }
+
+    let pivot_key = arr[median_of_three_by(arr, |a, b| a.0.cmp(&b.0))].0;
+    parallel_quicksort_helper(
+        arr,
+        |arr| par_partition3(arr, |x| x.0.cmp(&pivot_key)),
+        par_copy_parts,
+        |part, _| parallel_quicksort_pairs(part),
     );
+}
 
-    (less, eq, greater)
+/// Параллельно найти перестановку, сортирующую ключи: `keys[perm[0]] <= keys[perm[1]] <= ...`.
+/// Сортировка устойчива: равные ключи идут в порядке возрастания индексов.
+/// Сортируются пары `(ключ, индекс)` через [parallel_quicksort_pairs], а так как индексы различны,
+/// порядок пар полностью определен.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_argsort<T: Ord + Copy + Send + Sync>(keys: &[T]) -> Vec<usize> {
+    let mut indices = par_tabulate(keys.len(), |i| i);
+    let mut pairs = par_map_uninit(&indices, |&i| ((keys[i], i), ()));
+    parallel_quicksort_pairs(&mut pairs);
+    par_map_into(&pairs, &mut indices, |&((_, i), ())| i);
+    indices
 }
 
-/// Параллельная реализация быстрой сортировки, аналогичная [parallel_quicksort_seq_memcpy],
-/// но использующая параллельные примитивы из библиотеки `rayon`.
-pub fn rayon_parallel_quicksort<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) {
+/// Параллельно найти ранг каждого элемента: количество элементов массива, строго меньших его.
+/// Равные элементы получают одинаковый (минимальный в группе) ранг.
+///
+/// Массив упорядочивается [parallel_argsort], в отсортированном порядке ранг элемента -
+/// начало его группы равных, которое находится префиксным максимумом по началам групп.
+/// Затем ранги раскладываются обратно по исходным позициям обращением перестановки.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_rank<T: Ord + Copy + Send + Sync>(arr: &[T]) -> Vec<usize> {
+    let perm = parallel_argsort(arr);
+    let sorted = par_gather(arr, &perm);
+    let group_starts = par_tabulate(sorted.len(), |i| {
+        if i == 0 || sorted[i - 1] != sorted[i] {
+            i
+        } else {
+            0
+        }
+    });
+    let sorted_ranks = par_inclusive_scan(&group_starts, 0, cmp::max);
+    let mut ranks = vec![0; arr.len()];
+    // SAFETY: perm - перестановка, поэтому все индексы различны
+    unsafe { par_scatter(&sorted_ranks, &perm, &mut ranks) };
+    ranks
+}
+
+/// Аналог [parallel_quicksort_seq_memcpy] с заданной стратегией выбора опорного элемента.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_with_pivot<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    pivot: &(impl PivotSelector<T> + Sync),
+) {
     if arr.len() <= 4096 {
-        sequential_quicksort(arr);
+        sequential_quicksort_with_pivot(arr, pivot);
         return;
     }
 
-    let pivot = *arr.last().unwrap();
+    let pivot_value = arr[pivot.select(arr)];
+    parallel_quicksort_helper(
+        arr,
+        |arr| par_partition3(arr, |x| x.cmp(&pivot_value)),
+        copy_parts,
+        |part, _| parallel_quicksort_with_pivot(part, pivot),
+    );
+}
 
-    // Можно написать даже так, однако это выходит за рамки стандартных примитивов:
-    // let ((mut less, eq), (mut greater, _)): ((Vec<T>, Vec<T>), (Vec<T>, Vec<T>)) =
-    //     arr.par_iter().partition_map(|&x| match x {
-    //         x if x < pivot => Left(Left(x)),
-    //         x if x > pivot => Right(Left::<T, T>(x)),
-    //         _ => Left(Right(x)),
-    //     });
+/// Аналог [parallel_quicksort_par_memcpy], в котором подмассивы не длиннее `block`
+/// сортируются переданной функцией `base_case`
+/// (например, [insertion_sort], [slice::sort_unstable] или [sequential_quicksort]).
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_with_base_case<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    block: usize,
+    base_case: impl Fn(&mut [T]) + Copy + Sync,
+) {
+    if arr.len() <= block.max(1) {
+        base_case(arr);
+        return;
+    }
+
+    parallel_quicksort_helper(arr, parallel_partition, par_copy_parts, |part, _| {
+        parallel_quicksort_with_base_case(part, block, base_case)
+    });
+}
+
+/// Аналог [parallel_quicksort_with_pivot], отслеживающий глубину рекурсии.
+/// Возвращает максимальную достигнутую глубину (количество вложенных разбиений,
+/// включая последовательные).
+///
+/// Если глубина достигает `max_depth`, оставшийся подмассив сортируется через
+/// [slice::sort_unstable], поэтому даже при вырожденном выборе опорного элемента
+/// глубина рекурсии не превышает `max_depth`.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_depth_limited<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    pivot: &(impl PivotSelector<T> + Sync),
+    max_depth: usize,
+) -> usize {
+    parallel_quicksort_depth_limited_helper(arr, pivot, 0, max_depth)
+}
+
+#[cfg(feature = "handwritten")]
+fn parallel_quicksort_depth_limited_helper<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    pivot: &(impl PivotSelector<T> + Sync),
+    depth: usize,
+    max_depth: usize,
+) -> usize {
+    if arr.len() <= 4096 {
+        return sequential_quicksort_depth_limited(arr, pivot, depth, max_depth);
+    }
+    if depth == max_depth {
+        arr.sort_unstable();
+        return depth;
+    }
+
+    let pivot_value = arr[pivot.select(arr)];
+    let (less_depth, greater_depth) = parallel_quicksort_helper(
+        arr,
+        |arr| par_partition3(arr, |x| x.cmp(&pivot_value)),
+        copy_parts,
+        |part, _| parallel_quicksort_depth_limited_helper(part, pivot, depth + 1, max_depth),
+    );
+    less_depth.max(greater_depth)
+}
+
+#[cfg(feature = "handwritten")]
+fn sequential_quicksort_depth_limited<T: Ord>(
+    arr: &mut [T],
+    pivot: &impl PivotSelector<T>,
+    depth: usize,
+    max_depth: usize,
+) -> usize {
+    if arr.is_empty() {
+        return depth;
+    }
+    if depth == max_depth {
+        arr.sort_unstable();
+        return depth;
+    }
+    let last = arr.len() - 1;
+    arr.swap(pivot.select(arr), last);
+    let (eq_start, eq_end) = partition3(arr);
+    let left_depth =
+        sequential_quicksort_depth_limited(&mut arr[..eq_start], pivot, depth + 1, max_depth);
+    let right_depth =
+        sequential_quicksort_depth_limited(&mut arr[eq_end..], pivot, depth + 1, max_depth);
+    left_depth.max(right_depth)
+}
 
-    let mut less: Vec<T> = arr
-        .par_iter()
-        .filter_map(|&x| if x < pivot { Some(x) } else { None })
-        .collect();
-    let eq: Vec<T> = arr
-        .par_iter()
-        .filter_map(|&x| if x == pivot { Some(x) } else { None })
-        .collect();
-    let mut greater: Vec<T> = arr
-        .par_iter()
-        .filter_map(|&x| if x > pivot { Some(x) } else { None })
-        .collect();
+/// Переиспользуемые буферы для [parallel_quicksort_with_scratch].
+/// Буферы только растут, поэтому при повторных сортировках массивов похожего размера
+/// память почти не выделяется.
+#[cfg(feature = "handwritten")]
+#[derive(Debug, Default)]
+pub struct SortScratch<T> {
+    /// Результат разбиения, затем копируемый обратно в массив
+    buffer: Vec<T>,
+    /// Класс элемента относительно опорного: -1, 0 или 1
+    classes: Vec<i8>,
+    /// Позиции среди меньших опорного
+    less_positions: Vec<i32>,
+    /// Позиции среди больших опорного
+    greater_positions: Vec<i32>,
+}
+
+#[cfg(feature = "handwritten")]
+impl<T: Default + Clone> SortScratch<T> {
+    #[allow(dead_code)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn reserve(&mut self, len: usize) {
+        if self.buffer.len() < len {
+            self.buffer.resize(len, T::default());
+            self.classes.resize(len, 0);
+            self.less_positions.resize(len, 0);
+            self.greater_positions.resize(len, 0);
+        }
+    }
+}
+
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_par_memcpy],
+/// все временные массивы которой берутся из `scratch`.
+///
+/// Подмассивы, сортируемые параллельно, используют непересекающиеся части буферов,
+/// поэтому хватает буферов длины `arr.len()`. Память выделяется только
+/// под суммы блоков при подсчете префиксных сумм.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_with_scratch<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    scratch: &mut SortScratch<T>,
+) {
+    let len = arr.len();
+    scratch.reserve(len);
+    parallel_quicksort_scratch_helper(
+        arr,
+        &mut scratch.buffer[..len],
+        &mut scratch.classes[..len],
+        &mut scratch.less_positions[..len],
+        &mut scratch.greater_positions[..len],
+    );
+}
+
+#[cfg(feature = "handwritten")]
+fn parallel_quicksort_scratch_helper<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    buffer: &mut [T],
+    classes: &mut [i8],
+    less_positions: &mut [i32],
+    greater_positions: &mut [i32],
+) {
+    if arr.len() <= 4096 {
+        sequential_quicksort_3way(arr);
+        return;
+    }
+
+    // То же разбиение, что и в par_partition3, но сразу в один буфер:
+    // [меньшие, равные, большие]. Опорный элемент - медиана трех, как в parallel_partition
+    let pivot = arr[MedianOfThree.select(arr)];
+    par_map_into(arr, classes, |x| x.cmp(&pivot) as i8);
+    par_map_into(classes, less_positions, |&c| (c < 0) as i32);
+    par_map_into(classes, greater_positions, |&c| (c > 0) as i32);
+    let less_count = par_inline_prefix_sums(less_positions) as usize;
+    let greater_count = par_inline_prefix_sums(greater_positions) as usize;
+    let greater_start = arr.len() - greater_count;
 
+    let (arr_ref, classes_ref): (&[T], &[i8]) = (arr, classes);
+    let (less_ref, greater_ref): (&[i32], &[i32]) = (less_positions, greater_positions);
+    let buffer_ref = UnsafeSlice::new(buffer);
+    par_for_range(0, arr_ref.len(), |i| unsafe {
+        let (less_pos, greater_pos) = (less_ref[i] as usize, greater_ref[i] as usize);
+        let pos = match classes_ref[i].cmp(&0) {
+            cmp::Ordering::Less => less_pos,
+            cmp::Ordering::Equal => less_count + i - less_pos - greater_pos,
+            cmp::Ordering::Greater => greater_start + greater_pos,
+        };
+        buffer_ref.write(pos, arr_ref[i]);
+    });
+    par_copy(arr, buffer);
+
+    let (arr_less, arr_greater) = split_outer(arr, less_count, greater_start);
+    let (buffer_less, buffer_greater) = split_outer(buffer, less_count, greater_start);
+    let (classes_less, classes_greater) = split_outer(classes, less_count, greater_start);
+    let (less_less, less_greater) = split_outer(less_positions, less_count, greater_start);
+    let (greater_less, greater_greater) = split_outer(greater_positions, less_count, greater_start);
     rayon::join(
-        || rayon_parallel_quicksort(&mut less),
-        || rayon_parallel_quicksort(&mut greater),
+        || {
+            parallel_quicksort_scratch_helper(
+                arr_less,
+                buffer_less,
+                classes_less,
+                less_less,
+                greater_less,
+            )
+        },
+        || {
+            parallel_quicksort_scratch_helper(
+                arr_greater,
+                buffer_greater,
+                classes_greater,
+                less_greater,
+                greater_greater,
+            )
+        },
     );
+}
 
-    arr[0..less.len()].copy_from_slice(&less);
-    arr[less.len()..less.len() + eq.len()].copy_from_slice(&eq);
-    arr[less.len() + eq.len()..].copy_from_slice(&greater);
+/// Вернуть части слайса `[..l]` и `[r..]`.
+#[cfg(feature = "handwritten")]
+fn split_outer<U>(arr: &mut [U], l: usize, r: usize) -> (&mut [U], &mut [U]) {
+    let (left, rest) = arr.split_at_mut(l);
+    (left, &mut rest[r - l..])
 }
 
-///////////////////
-// Tests
-///////////////////
+/// Разбить непустой массив на три части: меньше, равные и больше опорного.
+/// Опорный элемент - медиана трех, поэтому упорядоченные данные делятся пополам.
+///
+/// Разбиение детерминировано: позиция каждого элемента в своей части определяется
+/// префиксными суммами по индексам, а не порядком выполнения задач.
+/// Поэтому и результат сортировки не зависит от планирования rayon,
+/// даже если равные элементы различимы (например, несут дополнительные данные).
+#[cfg(feature = "handwritten")]
+fn parallel_partition<T: Ord + Default + Copy + Send + Sync>(
+    arr: &[T],
+) -> (Vec<T>, Vec<T>, Vec<T>) {
+    let pivot = &arr[MedianOfThree.select(arr)];
+    par_partition3(arr, |x| x.cmp(pivot))
+}
 
-#[cfg(test)]
-mod tests {
-    use crate::sort::{
-        parallel_quicksort_3par_memcpy, parallel_quicksort_par_memcpy,
-        parallel_quicksort_seq_memcpy, rayon_parallel_quicksort, sequential_quicksort,
-        simple_parallel_quicksort,
-    };
-    use crate::utils::Random;
+/// Статистика работы быстрой сортировки.
+#[cfg(feature = "handwritten")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SortStats {
+    /// Общее количество разбиений по опорному элементу (включая последовательные)
+    pub partitions: usize,
+    /// Количество элементов меньше опорного на верхнем уровне
+    pub top_less: usize,
+    /// Количество элементов, равных опорному, на верхнем уровне
+    pub top_eq: usize,
+    /// Количество элементов больше опорного на верхнем уровне
+    pub top_greater: usize,
+}
 
-    #[test]
-    fn sort_test() {
-        let sorters: &[fn(&mut [i32])] = &[
-            sequential_quicksort,
-            simple_parallel_quicksort,
-            parallel_quicksort_seq_memcpy,
-            parallel_quicksort_3par_memcpy,
-            parallel_quicksort_par_memcpy,
-            rayon_parallel_quicksort,
-        ];
-        for sorter in sorters {
-            let mut random = Random::new(3);
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_seq_memcpy],
+/// дополнительно собирающая статистику разбиений.
+/// Позволяет обнаружить проблемы с выбором опорного элемента.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_with_stats<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+) -> SortStats {
+    if arr.is_empty() {
+        return SortStats::default();
+    }
 
-            for arr_len in [0, 10, 5000, 300_000] {
-                let mut arr = random.next_vec(arr_len);
-                let mut expected_arr = arr.clone();
-                expected_arr.sort();
+    let mut stats = SortStats::default();
+    let (less_partitions, greater_partitions) = parallel_quicksort_helper(
+        arr,
+        parallel_partition,
+        |arr, less, eq, greater| {
+            (stats.top_less, stats.top_eq, stats.top_greater) =
+                (less.len(), eq.len(), greater.len());
+            copy_parts(arr, less, eq, greater);
+        },
+        |part, _| parallel_quicksort_counting(part),
+    );
+    stats.partitions = 1 + less_partitions + greater_partitions;
+    stats
+}
 
-                sorter(&mut arr);
+/// Отсортировать массив и вернуть количество выполненных разбиений.
+#[cfg(feature = "handwritten")]
+fn parallel_quicksort_counting<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) -> usize {
+    if arr.len() <= 4096 {
+        return sequential_quicksort_counting(arr);
+    }
 
-                assert_eq!(expected_arr, arr);
-            }
+    let (less_partitions, greater_partitions) =
+        parallel_quicksort_helper(arr, parallel_partition, copy_parts, |part, _| {
+            parallel_quicksort_counting(part)
+        });
+    1 + less_partitions + greater_partitions
+}
+
+#[cfg(feature = "handwritten")]
+fn sequential_quicksort_counting<T: Ord>(arr: &mut [T]) -> usize {
+    if arr.is_empty() {
+        return 0;
+    }
+    let last = arr.len() - 1;
+    arr.swap(MedianOfThree.select(arr), last);
+    let (eq_start, eq_end) = partition3_one_pass(arr);
+    1 + sequential_quicksort_counting(&mut arr[..eq_start])
+        + sequential_quicksort_counting(&mut arr[eq_end..])
+}
+
+/// Эмпирическая оценка work и span параллельной сортировки,
+/// см. [parallel_quicksort_instrumented].
+#[cfg(feature = "instrumentation")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SortInstrumentation {
+    /// `joins_per_level[d]` - количество вызовов `rayon::join` на глубине рекурсии `d`
+    pub joins_per_level: Vec<usize>,
+    /// Сумма длин разбиваемых подмассивов плюс `m * ⌈log2 m⌉`
+    /// для каждого последовательно сортируемого блока длины `m`
+    pub work: usize,
+    /// Длина критического пути: максимум по путям от корня до листа суммы `⌈log2 m⌉`
+    /// по разбиениям подмассивов длины `m` (span разбиения с точностью до множителя)
+    /// плюс единица за лист, размер которого ограничен константой
+    pub span: usize,
+}
+
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_seq_memcpy],
+/// дополнительно оценивающая свои work и span.
+/// Work накапливается в атомарном счетчике, каждая задача знает свою глубину рекурсии,
+/// а span считается снизу вверх как максимум по двум подзадачам.
+#[cfg(feature = "instrumentation")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_instrumented<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+) -> SortInstrumentation {
+    let work = AtomicUsize::new(0);
+    let joins_per_level = Mutex::new(vec![]);
+    let span = parallel_quicksort_instrumented_helper(arr, 0, &work, &joins_per_level);
+    SortInstrumentation {
+        joins_per_level: joins_per_level.into_inner().unwrap(),
+        work: work.into_inner(),
+        span,
+    }
+}
+
+/// Отсортировать массив и вернуть span поддерева рекурсии.
+#[cfg(feature = "instrumentation")]
+fn parallel_quicksort_instrumented_helper<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    depth: usize,
+    work: &AtomicUsize,
+    joins_per_level: &Mutex<Vec<usize>>,
+) -> usize {
+    let log_len = arr.len().next_power_of_two().ilog2() as usize;
+    if arr.len() <= 4096 {
+        work.fetch_add(arr.len() * log_len, Ordering::Relaxed);
+        sequential_quicksort_3way(arr);
+        return 1;
+    }
+    work.fetch_add(arr.len(), Ordering::Relaxed);
+    {
+        let mut joins = joins_per_level.lock().unwrap();
+        if joins.len() <= depth {
+            joins.resize(depth + 1, 0);
+        }
+        joins[depth] += 1;
+    }
+
+    let (less_span, greater_span) =
+        parallel_quicksort_helper(arr, parallel_partition, copy_parts, |part, _| {
+            parallel_quicksort_instrumented_helper(part, depth + 1, work, joins_per_level)
+        });
+    log_len + cmp::max(less_span, greater_span)
+}
+
+/// Ошибка, возвращаемая прерванной сортировкой.
+#[cfg(feature = "handwritten")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_seq_memcpy],
+/// которую можно прервать, выставив флаг `cancel`.
+/// Флаг проверяется перед каждым разбиением. После прерывания массив остается
+/// частично отсортированным (но содержит те же элементы), возвращается `Err(Cancelled)`.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_cancellable<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    cancel: &AtomicBool,
+) -> Result<(), Cancelled> {
+    if cancel.load(Ordering::Relaxed) {
+        return Err(Cancelled);
+    }
+    if arr.len() <= 4096 {
+        sequential_quicksort_3way(arr);
+        return Ok(());
+    }
+
+    let (less_result, greater_result) =
+        parallel_quicksort_helper(arr, parallel_partition, copy_parts, |part, _| {
+            parallel_quicksort_cancellable(part, cancel)
+        });
+    less_result.and(greater_result)
+}
+
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_seq_memcpy],
+/// сообщающая о прогрессе: `on_progress` получает количество элементов,
+/// уже стоящих на своих итоговых местах.
+///
+/// Элементы считаются готовыми, когда они попадают в часть, равную опорному,
+/// или в отсортированный последовательно блок.
+/// Вызовы прореживаются (примерно раз в 1% массива), последний вызов получает `arr.len()`.
+/// Функция вызывается из разных задач, поэтому значения могут приходить не по порядку.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_with_progress<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    on_progress: impl Fn(usize) + Sync,
+) {
+    let total = arr.len();
+    let step = (total / 100).max(1);
+    let done = AtomicUsize::new(0);
+    let report = |finished: usize| {
+        let before = done.fetch_add(finished, Ordering::Relaxed);
+        let after = before + finished;
+        if after / step != before / step || after == total {
+            on_progress(after);
+        }
+    };
+    parallel_quicksort_progress_helper(arr, &report);
+}
+
+#[cfg(feature = "handwritten")]
+fn parallel_quicksort_progress_helper<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    report: &(impl Fn(usize) + Sync),
+) {
+    if arr.len() <= 4096 {
+        sequential_quicksort_3way(arr);
+        report(arr.len());
+        return;
+    }
+
+    parallel_quicksort_helper(
+        arr,
+        parallel_partition,
+        |arr, less, eq, greater| {
+            report(eq.len());
+            copy_parts(arr, less, eq, greater);
+        },
+        |part, _| parallel_quicksort_progress_helper(part, report),
+    );
+}
+
+/// Шаг разбиения быстрой сортировки.
+#[cfg(feature = "handwritten")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartitionEvent {
+    /// Начало разбиваемого подмассива в исходном массиве
+    pub start: usize,
+    /// Длина разбиваемого подмассива
+    pub len: usize,
+    /// Количество элементов подмассива, меньших опорного
+    /// (то есть позиция опорного элемента относительно `start`)
+    pub pivot_rank: usize,
+}
+
+/// Параллельная быстрая сортировка, аналогичная [parallel_quicksort_seq_memcpy],
+/// сообщающая о каждом разбиении (включая последовательные) через `sink`.
+/// Полезно для визуализации. Разбиения выполняются в разных задачах,
+/// поэтому события приходят в произвольном порядке.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_quicksort_traced<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    sink: impl Fn(PartitionEvent) + Sync,
+) {
+    parallel_quicksort_traced_helper(arr, 0, &sink);
+}
+
+#[cfg(feature = "handwritten")]
+fn parallel_quicksort_traced_helper<T: Ord + Default + Copy + Send + Sync>(
+    arr: &mut [T],
+    start: usize,
+    sink: &(impl Fn(PartitionEvent) + Sync),
+) {
+    if arr.len() <= 4096 {
+        sequential_quicksort_traced(arr, start, sink);
+        return;
+    }
+
+    parallel_quicksort_helper(
+        arr,
+        parallel_partition,
+        |arr, less, eq, greater| {
+            sink(PartitionEvent {
+                start,
+                len: arr.len(),
+                pivot_rank: less.len(),
+            });
+            copy_parts(arr, less, eq, greater);
+        },
+        |part, offset| parallel_quicksort_traced_helper(part, start + offset, sink),
+    );
+}
+
+#[cfg(feature = "handwritten")]
+fn sequential_quicksort_traced<T: Ord>(
+    arr: &mut [T],
+    start: usize,
+    sink: &impl Fn(PartitionEvent),
+) {
+    if arr.is_empty() {
+        return;
+    }
+    let last = arr.len() - 1;
+    arr.swap(MedianOfThree.select(arr), last);
+    let (eq_start, eq_end) = partition3_one_pass(arr);
+    sink(PartitionEvent {
+        start,
+        len: arr.len(),
+        pivot_rank: eq_start,
+    });
+    sequential_quicksort_traced(&mut arr[..eq_start], start, sink);
+    sequential_quicksort_traced(&mut arr[eq_end..], start + eq_end, sink);
+}
+
+/// Параллельная реализация быстрой сортировки, аналогичная [parallel_quicksort_seq_memcpy],
+/// но использующая параллельные примитивы из библиотеки `rayon`.
+#[cfg_attr(not(feature = "rayon-primitives"), allow(dead_code))]
+pub fn rayon_parallel_quicksort<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) {
+    if arr.len() <= 4096 {
+        sequential_quicksort_3way(arr);
+        return;
+    }
+
+    let pivot = arr[MedianOfThree.select(arr)];
+
+    // Можно написать даже так, однако это выходит за рамки стандартных примитивов:
+    // let ((mut less, eq), (mut greater, _)): ((Vec<T>, Vec<T>), (Vec<T>, Vec<T>)) =
+    //     arr.par_iter().partition_map(|&x| match x {
+    //         x if x < pivot => Left(Left(x)),
+    //         x if x > pivot => Right(Left::<T, T>(x)),
+    //         _ => Left(Right(x)),
+    //     });
+
+    let partition = |arr: &[T]| {
+        let less: Vec<T> = arr
+            .par_iter()
+            .filter_map(|&x| if x < pivot { Some(x) } else { None })
+            .collect();
+        let eq: Vec<T> = arr
+            .par_iter()
+            .filter_map(|&x| if x == pivot { Some(x) } else { None })
+            .collect();
+        let greater: Vec<T> = arr
+            .par_iter()
+            .filter_map(|&x| if x > pivot { Some(x) } else { None })
+            .collect();
+        (less, eq, greater)
+    };
+
+    parallel_quicksort_helper(arr, partition, copy_parts, |part, _| {
+        rayon_parallel_quicksort(part)
+    });
+}
+
+/// Параллельно найти k-ю порядковую статистику (k-й наименьший элемент, нумерация с нуля).
+/// Работает как быстрая сортировка, но рекурсивно спускается только в одну из частей.
+/// Опорный элемент - медиана трех, поэтому на упорядоченных данных
+/// каждый уровень отбрасывает около половины массива.
+#[cfg(feature = "handwritten")]
+pub fn parallel_select<T: Ord + Default + Copy + Send + Sync>(arr: &[T], k: usize) -> T {
+    parallel_select_with_pivot(arr, k, &MedianOfThree)
+}
+
+/// Аналог [parallel_select] с заданной стратегией выбора опорного элемента.
+/// С [MedianOfMedians](crate::pivot::MedianOfMedians) работает за O(n) в худшем случае.
+#[cfg(feature = "handwritten")]
+pub fn parallel_select_with_pivot<T: Ord + Default + Copy + Send + Sync>(
+    arr: &[T],
+    k: usize,
+    pivot: &(impl PivotSelector<T> + Sync),
+) -> T {
+    assert!(k < arr.len());
+    if arr.len() <= 4096 {
+        return sequential_select_with_pivot(&mut arr.to_vec(), k, pivot);
+    }
+
+    let pivot_value = arr[pivot.select(arr)];
+    let (less, eq, greater) = par_partition3(arr, |x| x.cmp(&pivot_value));
+
+    if k < less.len() {
+        parallel_select_with_pivot(&less, k, pivot)
+    } else if k < less.len() + eq.len() {
+        eq[0]
+    } else {
+        parallel_select_with_pivot(&greater, k - less.len() - eq.len(), pivot)
+    }
+}
+
+#[cfg(feature = "handwritten")]
+fn sequential_select_with_pivot<T: Ord + Copy>(
+    mut arr: &mut [T],
+    mut k: usize,
+    pivot: &impl PivotSelector<T>,
+) -> T {
+    loop {
+        let last = arr.len() - 1;
+        arr.swap(pivot.select(arr), last);
+        let (eq_start, eq_end) = partition3(arr);
+        if k < eq_start {
+            arr = &mut arr[..eq_start];
+        } else if k < eq_end {
+            return arr[eq_start];
+        } else {
+            k -= eq_end;
+            arr = &mut arr[eq_end..];
+        }
+    }
+}
+
+/// Параллельно найти `k` наибольших элементов без полной сортировки массива.
+/// Результат упорядочен по убыванию. При `k > arr.len()` возвращается весь массив.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_top_k<T: Ord + Default + Copy + Send + Sync>(arr: &[T], k: usize) -> Vec<T> {
+    if k == 0 {
+        return vec![];
+    }
+    let mut top = if k >= arr.len() {
+        arr.to_vec()
+    } else {
+        // k-й наибольший элемент
+        let threshold = parallel_select(arr, arr.len() - k);
+        let mut top = par_filter(arr, |&x| x > threshold);
+        top.resize(k, threshold);
+        top
+    };
+    parallel_quicksort_seq_memcpy(&mut top);
+    top.reverse();
+    top
+}
+
+/// Параллельная сортировка подсчетом для `u8`. O(log n) span (при фиксированном алфавите).
+///
+/// Каждый блок считает свою гистограмму, затем префиксные суммы по гистограммам,
+/// упорядоченным сначала по значению, а потом по номеру блока,
+/// дают каждому блоку позиции для записи его элементов.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_counting_sort_u8(arr: &mut [u8]) {
+    const COUNTING_BLOCK: usize = 4096;
+    const VALUES: usize = u8::MAX as usize + 1;
+
+    if arr.is_empty() {
+        return;
+    }
+
+    let block_count = arr.len().div_ceil(COUNTING_BLOCK);
+    // offsets[v * block_count + b] - сначала количество значений v в блоке b,
+    // после префиксных сумм - позиция первого такого значения в результате
+    let mut offsets = vec![0usize; VALUES * block_count];
+
+    let offsets_unsafe_slice = UnsafeSlice::new(&mut offsets);
+    blocked_for::<_, COUNTING_BLOCK>(arr, |block_num, block| {
+        let mut histogram = [0usize; VALUES];
+        block.iter().for_each(|&x| histogram[x as usize] += 1);
+        for (value, &count) in histogram.iter().enumerate() {
+            unsafe {
+                offsets_unsafe_slice.write(value * block_count + block_num, count);
+            }
+        }
+    });
+
+    par_inline_prefix_sums(&mut offsets);
+
+    let mut res = vec![0u8; arr.len()];
+    let res_unsafe_slice = UnsafeSlice::new(&mut res);
+    let offsets_ref: &[usize] = &offsets;
+    blocked_for::<_, COUNTING_BLOCK>(arr, |block_num, block| {
+        let mut positions = [0usize; VALUES];
+        for (value, position) in positions.iter_mut().enumerate() {
+            *position = offsets_ref[value * block_count + block_num];
+        }
+        for &x in block.iter() {
+            unsafe {
+                res_unsafe_slice.write(positions[x as usize], x);
+            }
+            positions[x as usize] += 1;
+        }
+    });
+
+    par_copy(arr, &res);
+}
+
+/// Параллельная сортировка слиянием отсортированных кусков.
+/// Сначала каждый кусок из `chunk` элементов (последний может быть неполным)
+/// независимо сортируется, что хорошо ложится в кэш, затем соседние отсортированные
+/// серии попарно сливаются [par_merge] по уровням сбалансированного дерева,
+/// поочередно через массив и буфер той же длины.
+#[cfg(feature = "handwritten")]
+#[allow(dead_code)]
+pub fn parallel_sort_chunked<T: Ord + Copy + Send + Sync>(arr: &mut [T], chunk: usize) {
+    blocked_for_dyn(arr, chunk, |_, block| sequential_quicksort_3way(block));
+
+    let mut buffer = par_map_uninit(arr, |&x| x);
+    let mut width = chunk;
+    let mut in_buffer = false;
+    while width < arr.len() {
+        if in_buffer {
+            merge_runs(&buffer, arr, width);
+        } else {
+            merge_runs(arr, &mut buffer, width);
         }
+        in_buffer = !in_buffer;
+        width = width.saturating_mul(2);
+    }
+    if in_buffer {
+        par_copy(arr, &buffer);
+    }
+}
+
+/// Слить пары соседних отсортированных серий длины `width` из `src` в `dst`.
+#[cfg(feature = "handwritten")]
+fn merge_runs<T: Ord + Copy + Send + Sync>(src: &[T], dst: &mut [T], width: usize) {
+    blocked_for_dyn(dst, width.saturating_mul(2), |pair_num, dst_pair| {
+        let start = pair_num * 2 * width;
+        let middle = cmp::min(start + width, src.len());
+        par_merge(
+            &src[start..middle],
+            &src[middle..start + dst_pair.len()],
+            dst_pair,
+        );
+    });
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "handwritten")]
+    use crate::parallel_primitives::{par_apply_permutation, par_gather};
+    #[cfg(feature = "handwritten")]
+    use crate::pivot::MedianOfMedians;
+    use crate::pivot::{LastElement, MedianOfThree, PivotSelector, RandomPivot};
+    use crate::sort::{
+        insertion_sort, parallel_quicksort_by_key, parallel_quicksort_ordered,
+        parallel_quicksort_try_by, parallel_sort_rows, partition3, partition3_one_pass,
+        rayon_parallel_quicksort, sequential_quicksort, sequential_quicksort_3way,
+        sequential_quicksort_counted, sequential_quicksort_with_pivot, simple_parallel_quicksort,
+        simple_parallel_quicksort_with_pivot, sort_network, SortOrder,
+    };
+    #[cfg(feature = "handwritten")]
+    use crate::sort::{
+        parallel_argsort, parallel_counting_sort_u8, parallel_quicksort_3par_memcpy,
+        parallel_quicksort_by_cached_key, parallel_quicksort_cancellable,
+        parallel_quicksort_cloned, parallel_quicksort_compact, parallel_quicksort_depth_limited,
+        parallel_quicksort_out, parallel_quicksort_pairs, parallel_quicksort_par_memcpy,
+        parallel_quicksort_seq_memcpy, parallel_quicksort_traced,
+        parallel_quicksort_with_base_case, parallel_quicksort_with_pivot,
+        parallel_quicksort_with_progress, parallel_quicksort_with_scratch,
+        parallel_quicksort_with_stats, parallel_rank, parallel_select, parallel_select_with_pivot,
+        parallel_sort_chunked, parallel_top_k, Cancelled, SortScratch, SortStats,
+    };
+    #[cfg(feature = "instrumentation")]
+    use crate::sort::{parallel_quicksort_instrumented, SortInstrumentation};
+    use crate::utils::Random;
+    use std::cell::Cell;
+    use std::num::ParseIntError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    #[cfg(feature = "handwritten")]
+    use std::sync::{atomic::AtomicBool, Barrier, Mutex};
+
+    #[test]
+    fn sort_test() {
+        #[cfg_attr(not(feature = "handwritten"), allow(unused_mut))]
+        let mut sorters: Vec<fn(&mut [i32])> = vec![
+            sequential_quicksort,
+            simple_parallel_quicksort,
+            rayon_parallel_quicksort,
+        ];
+        #[cfg(feature = "handwritten")]
+        sorters.extend_from_slice(&[
+            parallel_quicksort_seq_memcpy,
+            parallel_quicksort_3par_memcpy,
+            parallel_quicksort_par_memcpy,
+        ]);
+        for sorter in sorters {
+            let mut random = Random::new(3);
+
+            for arr_len in [0, 10, 5000, 300_000] {
+                let mut arr = random.next_vec(arr_len);
+                let mut expected_arr = arr.clone();
+                expected_arr.sort();
+
+                sorter(&mut arr);
+
+                assert_eq!(expected_arr, arr);
+            }
+        }
+    }
+
+    #[test]
+    fn sequential_quicksort_all_equal_test() {
+        // С двухпутевым разбиением глубина рекурсии была бы равна длине массива
+        let mut arr = vec![7; 500_000];
+        sequential_quicksort(&mut arr);
+        assert!(arr.iter().all(|&x| x == 7));
+
+        let mut arr = vec![7; 500_000];
+        sequential_quicksort_with_pivot(&mut arr, &LastElement);
+        assert!(arr.iter().all(|&x| x == 7));
+
+        let mut arr = Random::new(3).next_vec_in_range(500_000, 0, 3);
+        let mut expected_arr = arr.clone();
+        expected_arr.sort();
+        sequential_quicksort(&mut arr);
+        assert_eq!(expected_arr, arr);
+    }
+
+    #[test]
+    fn sequential_quicksort_3way_test() {
+        /// Число, считающее сравнения в переданном счетчике
+        #[derive(Clone, Copy, Debug)]
+        struct Counted<'a>(i32, &'a Cell<usize>);
+
+        impl PartialEq for Counted<'_> {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == std::cmp::Ordering::Equal
+            }
+        }
+
+        impl Eq for Counted<'_> {}
+
+        impl PartialOrd for Counted<'_> {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Counted<'_> {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.1.set(self.1.get() + 1);
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 17, 1000, 100_000] {
+            let mut arr = random.next_vec(arr_len);
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+            sequential_quicksort_3way(&mut arr);
+            assert_eq!(expected_arr, arr);
+        }
+
+        // Всего 5 различных значений: глубина рекурсии не больше 5,
+        // и на каждом уровне каждый элемент сравнивается один раз
+        let arr_len = 1_000_000;
+        let comparisons = Cell::new(0);
+        let mut arr: Vec<Counted> = random
+            .next_vec_in_range(arr_len, 0, 5)
+            .into_iter()
+            .map(|x| Counted(x, &comparisons))
+            .collect();
+        let mut expected_arr: Vec<i32> = arr.iter().map(|x| x.0).collect();
+        expected_arr.sort();
+
+        sequential_quicksort_3way(&mut arr);
+
+        assert_eq!(expected_arr, arr.iter().map(|x| x.0).collect::<Vec<_>>());
+        assert!(comparisons.get() <= 5 * arr_len);
+    }
+
+    #[test]
+    fn sort_few_distinct_values_test() {
+        /// Ключ, считающий сравнения в общем счетчике
+        #[cfg_attr(not(feature = "handwritten"), allow(dead_code))]
+        #[derive(Clone, Copy, Debug)]
+        struct CountedKey<'a>(i32, &'a AtomicUsize);
+
+        impl PartialEq for CountedKey<'_> {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == std::cmp::Ordering::Equal
+            }
+        }
+
+        impl Eq for CountedKey<'_> {}
+
+        impl PartialOrd for CountedKey<'_> {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for CountedKey<'_> {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.1.fetch_add(1, Ordering::Relaxed);
+                self.0.cmp(&other.0)
+            }
+        }
+
+        // Всего 5 различных ключей: при трехпутевом разбиении глубина рекурсии не больше 5,
+        // а при двухпутевом блоки из равных ключей сортировались бы за квадрат
+        let mut random = Random::new(3);
+        let arr_len = 300_000;
+        let keys = random.next_vec_in_range(arr_len, 0, 5);
+        let mut expected_keys = keys.clone();
+        expected_keys.sort();
+
+        let key_calls = AtomicUsize::new(0);
+        let mut arr = keys.clone();
+        parallel_quicksort_by_key(&mut arr, |&x| {
+            key_calls.fetch_add(1, Ordering::Relaxed);
+            x
+        });
+        assert_eq!(expected_keys, arr);
+        assert!(key_calls.into_inner() <= 2 * 5 * arr_len);
+
+        let mut arr = keys.clone();
+        parallel_quicksort_ordered(&mut arr, SortOrder::Descending);
+        expected_keys.reverse();
+        assert_eq!(expected_keys, arr);
+        expected_keys.reverse();
+
+        #[cfg(feature = "handwritten")]
+        {
+            let comparisons = AtomicUsize::new(0);
+            let mut arr: Vec<(CountedKey, usize)> = keys
+                .iter()
+                .enumerate()
+                .map(|(i, &key)| (CountedKey(key, &comparisons), i))
+                .collect();
+            parallel_quicksort_pairs(&mut arr);
+            let sorted_keys: Vec<i32> = arr.iter().map(|(key, _)| key.0).collect();
+            assert_eq!(expected_keys, sorted_keys);
+            assert!(comparisons.into_inner() <= 2 * 5 * arr_len);
+        }
+    }
+
+    #[test]
+    fn partition3_one_pass_test() {
+        let mut random = Random::new(3);
+        for arr_len in [1, 2, 10, 1000] {
+            let mut arr = random.next_vec_in_range(arr_len, 0, 5);
+            let pivot = arr[arr_len - 1];
+
+            let (eq_start, eq_end) = partition3_one_pass(&mut arr);
+
+            assert!(arr[..eq_start].iter().all(|&x| x < pivot));
+            assert!(arr[eq_start..eq_end].iter().all(|&x| x == pivot));
+            assert!(arr[eq_end..].iter().all(|&x| x > pivot));
+        }
+    }
+
+    #[test]
+    fn partition3_test() {
+        let mut random = Random::new(3);
+        for arr_len in [1, 2, 10, 1000] {
+            let mut arr = random.next_vec_in_range(arr_len, 0, 5);
+            let pivot = arr[arr_len - 1];
+
+            let (eq_start, eq_end) = partition3(&mut arr);
+
+            assert!(arr[..eq_start].iter().all(|&x| x < pivot));
+            assert!(arr[eq_start..eq_end].iter().all(|&x| x == pivot));
+            assert!(arr[eq_end..].iter().all(|&x| x > pivot));
+        }
+    }
+
+    #[test]
+    fn sequential_quicksort_counted_test() {
+        let n = 100_000;
+        let mut arr = Random::new(3).next_vec(n);
+        let mut expected_arr = arr.clone();
+        expected_arr.sort();
+
+        let comparisons = sequential_quicksort_counted(&mut arr);
+
+        assert_eq!(expected_arr, arr);
+        // Около 1.6 n log n сравнений: часть уходит на второй проход трехпутевого разбиения
+        let n_log_n = n as f64 * (n as f64).log2();
+        assert!((n_log_n as u64..2 * n_log_n as u64).contains(&comparisons));
+
+        // Медиана трех делит упорядоченные данные пополам
+        let mut sorted: Vec<i32> = (0..n as i32).collect();
+        assert!(sequential_quicksort_counted(&mut sorted) < 2 * n_log_n as u64);
+        let mut reversed: Vec<i32> = (0..n as i32).rev().collect();
+        assert!(sequential_quicksort_counted(&mut reversed) < 3 * n_log_n as u64);
+        assert_eq!(sorted, reversed);
+
+        // Два сравнения на медиану трех и по одному на каждый элемент в двух проходах
+        // трехпутевого разбиения, после которого рекурсия не нужна
+        let mut equal = vec![7; n];
+        assert_eq!(2 * n as u64 + 1, sequential_quicksort_counted(&mut equal));
+    }
+
+    #[test]
+    fn sort_network_test() {
+        /// По принципу нулей и единиц сеть сортирует все входы,
+        /// если она сортирует все последовательности из нулей и единиц.
+        /// Для маленьких N дополнительно перебираются все перестановки.
+        fn check<const N: usize>() {
+            for bits in 0..1u32 << N {
+                let mut arr: [u32; N] = std::array::from_fn(|i| (bits >> i) & 1);
+                sort_network(&mut arr);
+                assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+            }
+            if N <= 8 {
+                let mut perm: [usize; N] = std::array::from_fn(|i| i);
+                check_permutations(&mut perm, 0);
+            }
+        }
+
+        fn check_permutations<const N: usize>(perm: &mut [usize; N], fixed: usize) {
+            if fixed == N {
+                let mut arr = *perm;
+                sort_network(&mut arr);
+                assert_eq!(std::array::from_fn::<usize, N, _>(|i| i), arr);
+                return;
+            }
+            for i in fixed..N {
+                perm.swap(fixed, i);
+                check_permutations(perm, fixed + 1);
+                perm.swap(fixed, i);
+            }
+        }
+
+        check::<0>();
+        check::<1>();
+        check::<2>();
+        check::<3>();
+        check::<4>();
+        check::<5>();
+        check::<6>();
+        check::<7>();
+        check::<8>();
+        check::<9>();
+        check::<10>();
+        check::<11>();
+        check::<12>();
+        check::<13>();
+        check::<14>();
+        check::<15>();
+        check::<16>();
+    }
+
+    #[test]
+    fn insertion_sort_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 2, 10, 1000] {
+            let mut arr = random.next_vec_in_range(arr_len, -100, 100);
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            insertion_sort(&mut arr);
+
+            assert_eq!(expected_arr, arr);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_with_base_case_test() {
+        fn check(block: usize, base_case: fn(&mut [i32])) {
+            let mut random = Random::new(3);
+            for arr_len in [0, 10, 5000, 100_000] {
+                let mut arr = random.next_vec(arr_len);
+                let mut expected_arr = arr.clone();
+                expected_arr.sort();
+
+                parallel_quicksort_with_base_case(&mut arr, block, base_case);
+
+                assert_eq!(expected_arr, arr);
+            }
+        }
+
+        check(16, insertion_sort);
+        check(4096, <[i32]>::sort_unstable);
+        check(4096, sequential_quicksort);
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_single_thread_test() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let sorters: &[fn(&mut [i32])] = &[
+            parallel_quicksort_seq_memcpy,
+            parallel_quicksort_3par_memcpy,
+            parallel_quicksort_par_memcpy,
+        ];
+        pool.install(|| {
+            for sorter in sorters {
+                let mut random = Random::new(3);
+                for arr_len in [0, 10, 5000, 100_000] {
+                    let mut arr = random.next_vec(arr_len);
+                    let mut expected_arr = arr.clone();
+                    expected_arr.sort();
+
+                    sorter(&mut arr);
+
+                    assert_eq!(expected_arr, arr);
+                }
+            }
+        });
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_partition_copy_back_test() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let mut random = Random::new(3);
+        let arr_len = 300_000;
+        let inputs = [
+            random.next_vec(arr_len),
+            random.next_vec_in_range(arr_len, -10, 10),
+            vec![7; arr_len],
+        ];
+        let sorters: [fn(&mut [i32]); 3] = [
+            parallel_quicksort_seq_memcpy,
+            parallel_quicksort_3par_memcpy,
+            parallel_quicksort_par_memcpy,
+        ];
+        for input in &inputs {
+            let mut expected_arr = input.clone();
+            expected_arr.sort();
+            for sorter in sorters {
+                let mut arr = input.clone();
+                pool.install(|| sorter(&mut arr));
+                assert_eq!(expected_arr, arr);
+            }
+        }
+    }
+
+    /// Части разбиения освобождаются до рекурсии, поэтому дополнительная память
+    /// около двух размеров массива, а не растет с глубиной рекурсии
+    /// (раньше на таком массиве было около пяти).
+    /// Счетчик общий для всех тестов, поэтому граница взята с запасом.
+    #[cfg(all(feature = "handwritten", feature = "peak-memory"))]
+    #[test]
+    fn sort_peak_memory_test() {
+        let mut random = Random::new(3);
+        let mut arr = random.next_vec_of::<i64>(1 << 22);
+        let arr_bytes = arr.len() * size_of::<i64>();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+
+        crate::ALLOCATOR.reset_peak();
+        let base_memory = crate::ALLOCATOR.current();
+        pool.install(|| parallel_quicksort_seq_memcpy(&mut arr));
+        let peak_extra = crate::ALLOCATOR.peak() - base_memory;
+
+        assert!(arr.is_sorted());
+        assert!(peak_extra < 3 * arr_bytes);
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_compact_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 300_000] {
+            let mut arr = Vec::with_capacity(2 * arr_len + 100);
+            arr.extend(random.next_vec(arr_len));
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            parallel_quicksort_compact(&mut arr);
+
+            assert_eq!(expected_arr, arr);
+            assert!(arr.capacity() < 2 * arr_len + 100);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_with_scratch_test() {
+        let mut random = Random::new(3);
+        let mut scratch = SortScratch::new();
+        let mut inputs: Vec<Vec<i32>> = (0..100)
+            .map(|i| {
+                let arr_len = if i % 10 == 0 { 100_000 } else { i * 200 };
+                random.next_vec_in_range(arr_len, -1000, 1000)
+            })
+            .collect();
+        inputs.push((0..100_000).collect());
+        inputs.push(vec![7; 100_000]);
+        for mut arr in inputs {
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            parallel_quicksort_with_scratch(&mut arr, &mut scratch);
+
+            assert_eq!(expected_arr, arr);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_out_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 5000, 300_000] {
+            let src = random.next_vec(arr_len);
+            let src_copy = src.clone();
+            let mut expected_arr = src.clone();
+            expected_arr.sort();
+
+            let mut dst = vec![0; arr_len];
+            parallel_quicksort_out(&src, &mut dst);
+
+            assert_eq!(expected_arr, dst);
+            assert_eq!(src_copy, src);
+        }
+    }
+
+    #[cfg(feature = "instrumentation")]
+    #[test]
+    fn sort_instrumented_test() {
+        let mut random = Random::new(3);
+        for log_len in [16, 18, 20] {
+            let arr_len = 1 << log_len;
+            let mut arr = random.next_vec(arr_len);
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            let stats = parallel_quicksort_instrumented(&mut arr);
+
+            assert_eq!(expected_arr, arr);
+            // work ~ n log n
+            let n_log_n = arr_len * log_len;
+            assert!((n_log_n / 2..2 * n_log_n).contains(&stats.work));
+            // span ~ log^2 n
+            assert!(stats.span <= 2 * log_len * log_len);
+            assert_eq!(1, stats.joins_per_level[0]);
+            assert!(stats.joins_per_level.iter().all(|&joins| joins > 0));
+        }
+
+        let mut arr = vec![1, 3, 2];
+        assert_eq!(
+            SortInstrumentation {
+                joins_per_level: vec![],
+                work: 6,
+                span: 1,
+            },
+            parallel_quicksort_instrumented(&mut arr)
+        );
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_with_stats_test() {
+        let mut random = Random::new(3);
+        let mut inputs: Vec<Vec<i32>> = [10, 5000, 300_000]
+            .into_iter()
+            .map(|arr_len| random.next_vec(arr_len))
+            .collect();
+        inputs.push((0..300_000).collect());
+        for mut arr in inputs {
+            let arr_len = arr.len();
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            let stats = parallel_quicksort_with_stats(&mut arr);
+
+            assert_eq!(expected_arr, arr);
+            assert_eq!(arr_len, stats.top_less + stats.top_eq + stats.top_greater);
+            assert!(stats.partitions >= 1);
+        }
+
+        assert_eq!(
+            SortStats::default(),
+            parallel_quicksort_with_stats::<i32>(&mut [])
+        );
+
+        let mut arr = vec![7; 10_000];
+        let stats = parallel_quicksort_with_stats(&mut arr);
+        assert_eq!(
+            SortStats {
+                partitions: 1,
+                top_less: 0,
+                top_eq: 10_000,
+                top_greater: 0,
+            },
+            stats
+        );
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_cancellable_test() {
+        let mut random = Random::new(3);
+        let never_cancelled = AtomicBool::new(false);
+        let cancelled = AtomicBool::new(true);
+        for arr_len in [0, 10, 5000, 300_000] {
+            let mut arr = random.next_vec(arr_len);
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            let input = arr.clone();
+            assert_eq!(
+                Err(Cancelled),
+                parallel_quicksort_cancellable(&mut arr, &cancelled)
+            );
+            assert_eq!(input, arr);
+
+            assert_eq!(
+                Ok(()),
+                parallel_quicksort_cancellable(&mut arr, &never_cancelled)
+            );
+            assert_eq!(expected_arr, arr);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_cancelled_during_sort_test() {
+        // Первое сравнение ждет, пока другой поток выставит флаг,
+        // поэтому флаг гарантированно меняется уже после начала сортировки
+        static STARTED: Barrier = Barrier::new(2);
+        static FIRST_COMPARISON: AtomicBool = AtomicBool::new(true);
+        static CANCEL: AtomicBool = AtomicBool::new(false);
+
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        struct Gated(i32);
+
+        impl PartialOrd for Gated {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Gated {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                if FIRST_COMPARISON.swap(false, Ordering::Relaxed) {
+                    STARTED.wait();
+                    while !CANCEL.load(Ordering::Relaxed) {
+                        std::thread::yield_now();
+                    }
+                }
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut random = Random::new(3);
+        let input: Vec<Gated> = random.next_vec(300_000).into_iter().map(Gated).collect();
+        let mut arr = input.clone();
+        let result = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                STARTED.wait();
+                CANCEL.store(true, Ordering::Relaxed);
+            });
+            parallel_quicksort_cancellable(&mut arr, &CANCEL)
+        });
+        assert_eq!(Err(Cancelled), result);
+
+        // Элементы не теряются и не дублируются
+        let mut expected_arr = input;
+        expected_arr.sort();
+        arr.sort();
+        assert_eq!(expected_arr, arr);
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_with_progress_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 5000, 300_000] {
+            let mut arr = random.next_vec(arr_len);
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            let max_progress = AtomicUsize::new(0);
+            let calls = AtomicUsize::new(0);
+            parallel_quicksort_with_progress(&mut arr, |progress| {
+                max_progress.fetch_max(progress, Ordering::Relaxed);
+                calls.fetch_add(1, Ordering::Relaxed);
+            });
+
+            assert_eq!(expected_arr, arr);
+            assert_eq!(arr_len, max_progress.load(Ordering::Relaxed));
+            assert!(calls.load(Ordering::Relaxed) <= 101);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_traced_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 5000, 300_000] {
+            // Различные значения: каждый элемент становится опорным ровно один раз
+            let mut arr: Vec<i64> = random
+                .next_vec(arr_len)
+                .into_iter()
+                .enumerate()
+                .map(|(i, x)| x as i64 * arr_len as i64 + i as i64)
+                .collect();
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            let events = Mutex::new(vec![]);
+            parallel_quicksort_traced(&mut arr, |event| events.lock().unwrap().push(event));
+
+            assert_eq!(expected_arr, arr);
+            let events = events.into_inner().unwrap();
+            assert!(events
+                .iter()
+                .all(|event| event.pivot_rank < event.len && event.start + event.len <= arr_len));
+            let mut pivot_positions: Vec<usize> = events
+                .iter()
+                .map(|event| event.start + event.pivot_rank)
+                .collect();
+            pivot_positions.sort();
+            assert_eq!((0..arr_len).collect::<Vec<_>>(), pivot_positions);
+            if arr_len > 0 {
+                let top = events.iter().max_by_key(|event| event.len).unwrap();
+                assert_eq!((0, arr_len), (top.start, top.len));
+            }
+        }
+
+        // На упорядоченных данных опорный элемент (медиана трех) всегда делит подмассив пополам,
+        // как в последовательных листах, так и в параллельных разбиениях
+        let mut arr: Vec<i64> = (0..300_000).collect();
+        let events = Mutex::new(vec![]);
+        parallel_quicksort_traced(&mut arr, |event| events.lock().unwrap().push(event));
+        assert!(arr.windows(2).all(|w| w[0] < w[1]));
+        assert!(events
+            .into_inner()
+            .unwrap()
+            .iter()
+            .all(|event| event.pivot_rank == event.len / 2));
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn counting_sort_u8_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 5000, 300_000] {
+            let mut arr: Vec<u8> = random
+                .next_vec(arr_len)
+                .into_iter()
+                .map(|x| x as u8)
+                .collect();
+            let mut expected_arr = arr.clone();
+            expected_arr.sort_unstable();
+
+            parallel_counting_sort_u8(&mut arr);
+
+            assert_eq!(expected_arr, arr);
+        }
+
+        let mut arr = vec![42u8; 100_000];
+        parallel_counting_sort_u8(&mut arr);
+        assert_eq!(vec![42u8; 100_000], arr);
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn select_test() {
+        let mut random = Random::new(3);
+        for arr_len in [1, 10, 5000, 300_000] {
+            let arr = random.next_vec_in_range(arr_len, -1000, 1000);
+            let mut sorted_arr = arr.clone();
+            sorted_arr.sort();
+
+            for k in [0, arr_len / 3, arr_len - 1] {
+                assert_eq!(sorted_arr[k], parallel_select(&arr, k));
+            }
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn top_k_test() {
+        let mut random = Random::new(3);
+        let mut inputs: Vec<Vec<i32>> = [0, 10, 5000, 300_000]
+            .into_iter()
+            .map(|arr_len| random.next_vec_in_range(arr_len, -1000, 1000))
+            .collect();
+        inputs.push((0..300_000).collect());
+        inputs.push((0..300_000).rev().collect());
+        for arr in inputs {
+            let arr_len = arr.len();
+            let mut expected = arr.clone();
+            expected.sort_by(|a, b| b.cmp(a));
+
+            assert_eq!(Vec::<i32>::new(), parallel_top_k(&arr, 0));
+            assert_eq!(expected[..arr_len.min(1)], parallel_top_k(&arr, 1));
+            assert_eq!(expected[..arr_len.min(100)], parallel_top_k(&arr, 100));
+            assert_eq!(expected, parallel_top_k(&arr, arr_len + 5));
+        }
+    }
+
+    type Pair = (i32, i32);
+
+    #[test]
+    fn sort_by_key_test() {
+        #[cfg_attr(not(feature = "handwritten"), allow(unused_mut))]
+        let mut sorters: Vec<fn(&mut [Pair])> =
+            vec![|arr| parallel_quicksort_by_key(arr, |pair| pair.1)];
+        #[cfg(feature = "handwritten")]
+        sorters.push(|arr| parallel_quicksort_by_cached_key(arr, |pair| pair.1));
+        for sorter in sorters {
+            let mut random = Random::new(3);
+
+            for arr_len in [0, 10, 5000, 300_000] {
+                let firsts = random.next_vec(arr_len);
+                let seconds = random.next_vec_in_range(arr_len, -1000, 1000);
+                let mut arr: Vec<Pair> = firsts.into_iter().zip(seconds).collect();
+                let mut expected_arr = arr.clone();
+                expected_arr.sort_by_key(|pair| pair.1);
+
+                sorter(&mut arr);
+
+                assert!(arr.windows(2).all(|w| w[0].1 <= w[1].1));
+                // Сортировка неустойчивая, поэтому сравниваем мультимножества
+                arr.sort();
+                expected_arr.sort();
+                assert_eq!(expected_arr, arr);
+            }
+        }
+    }
+
+    #[test]
+    fn sort_rows_test() {
+        let mut random = Random::new(3);
+        for rows_count in [0, 10, 5000, 100_000] {
+            let mut rows: Vec<[i64; 3]> = (0..rows_count)
+                .map(|_| {
+                    [
+                        random.next_i64_in_range(-1000, 1000),
+                        random.next_i64_in_range(-1000, 1000),
+                        random.next_i64_in_range(-1000, 1000),
+                    ]
+                })
+                .collect();
+            let mut expected_rows = rows.clone();
+
+            parallel_sort_rows(&mut rows, 1);
+
+            assert!(rows.windows(2).all(|w| w[0][1] <= w[1][1]));
+            rows.sort();
+            expected_rows.sort();
+            assert_eq!(expected_rows, rows);
+        }
+    }
+
+    #[test]
+    fn sort_try_by_test() {
+        let parse_cmp = |a: &String, b: &String| -> Result<std::cmp::Ordering, ParseIntError> {
+            Ok(a.parse::<i32>()?.cmp(&b.parse::<i32>()?))
+        };
+
+        let mut random = Random::new(3);
+        let numbers = random.next_vec_in_range(100_000, -1000, 1000);
+        let mut arr: Vec<String> = numbers.iter().map(|x| x.to_string()).collect();
+        assert_eq!(Ok(()), parallel_quicksort_try_by(&mut arr, parse_cmp));
+        let mut expected_numbers = numbers.clone();
+        expected_numbers.sort();
+        let sorted_numbers: Vec<i32> = arr.iter().map(|x| x.parse().unwrap()).collect();
+        assert_eq!(expected_numbers, sorted_numbers);
+
+        let mut arr: Vec<String> = (0..100_000).map(|x| x.to_string()).collect();
+        let expected_arr = arr.clone();
+        assert_eq!(Ok(()), parallel_quicksort_try_by(&mut arr, parse_cmp));
+        assert_eq!(expected_arr, arr);
+
+        let mut arr: Vec<String> = numbers.iter().map(|x| x.to_string()).collect();
+        arr[12_345] = "not a number".to_string();
+        let mut expected_arr = arr.clone();
+
+        assert!(parallel_quicksort_try_by(&mut arr, parse_cmp).is_err());
+
+        // Элементы не теряются и не дублируются
+        arr.sort();
+        expected_arr.sort();
+        assert_eq!(expected_arr, arr);
+    }
+
+    #[test]
+    fn sort_ordered_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 5000, 300_000] {
+            let arr = random.next_vec_in_range(arr_len, -1000, 1000);
+
+            let mut ascending = arr.clone();
+            parallel_quicksort_ordered(&mut ascending, SortOrder::Ascending);
+            let mut expected = arr.clone();
+            expected.sort();
+            assert_eq!(expected, ascending);
+
+            let mut descending = arr.clone();
+            parallel_quicksort_ordered(&mut descending, SortOrder::Descending);
+            expected.reverse();
+            assert_eq!(expected, descending);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_sorted_test() {
+        let mut random = Random::new(3);
+        let mut arr = random.next_vec(100_000);
+        arr.sort();
+        let expected_arr = arr.clone();
+
+        parallel_quicksort_par_memcpy(&mut arr);
+
+        assert_eq!(expected_arr, arr);
+    }
+
+    /// Тип, который можно только клонировать
+    #[cfg(feature = "handwritten")]
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct CloneOnly(i64);
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_pairs_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 10, 5000, 300_000] {
+            let keys = random.next_vec_in_range(arr_len, -1000, 1000);
+            let payloads: Vec<u64> = random.next_vec_of(arr_len);
+            let mut arr: Vec<(i32, u64)> = keys.into_iter().zip(payloads).collect();
+            let mut expected_arr = arr.clone();
+            expected_arr.sort_by_key(|&(key, _)| key);
+
+            parallel_quicksort_pairs(&mut arr);
+
+            let keys = |arr: &[(i32, u64)]| arr.iter().map(|&(key, _)| key).collect::<Vec<_>>();
+            assert_eq!(keys(&expected_arr), keys(&arr));
+            let mut expected_pairs = expected_arr.clone();
+            let mut actual_pairs = arr.clone();
+            expected_pairs.sort();
+            actual_pairs.sort();
+            assert_eq!(expected_pairs, actual_pairs);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_cloned_test() {
+        let mut random = Random::new(3);
+        let mut inputs: Vec<Vec<i32>> = [0, 10, 5000, 300_000]
+            .into_iter()
+            .map(|arr_len| random.next_vec(arr_len))
+            .collect();
+        inputs.push((0..300_000).collect());
+        for input in inputs {
+            let mut arr: Vec<CloneOnly> = input.into_iter().map(|x| CloneOnly(x as i64)).collect();
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            parallel_quicksort_cloned(&mut arr);
+
+            assert_eq!(expected_arr, arr);
+        }
+    }
+
+    /// Элемент, сравниваемый только по ключу
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Keyed {
+        key: i32,
+        payload: u32,
+    }
+
+    impl PartialEq for Keyed {
+        fn eq(&self, other: &Self) -> bool {
+            self.key == other.key
+        }
+    }
+
+    impl Eq for Keyed {}
+
+    impl PartialOrd for Keyed {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Keyed {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.key.cmp(&other.key)
+        }
+    }
+
+    #[test]
+    fn sort_deterministic_test() {
+        #[cfg_attr(not(feature = "handwritten"), allow(unused_mut))]
+        let mut sorters: Vec<fn(&mut [Keyed])> =
+            vec![simple_parallel_quicksort, rayon_parallel_quicksort];
+        #[cfg(feature = "handwritten")]
+        sorters.extend_from_slice(&[
+            parallel_quicksort_seq_memcpy,
+            parallel_quicksort_3par_memcpy,
+            parallel_quicksort_par_memcpy,
+        ]);
+        let mut random = Random::new(3);
+        let arr: Vec<Keyed> = random
+            .next_vec_in_range(10_000, 0, 1000)
+            .into_iter()
+            .map(|key| Keyed {
+                key,
+                payload: random.next(),
+            })
+            .collect();
+
+        for sorter in sorters {
+            let mut first = arr.clone();
+            sorter(&mut first);
+            let first_payloads: Vec<u32> = first.iter().map(|x| x.payload).collect();
+
+            for _ in 0..50 {
+                let mut sorted = arr.clone();
+                sorter(&mut sorted);
+                let payloads: Vec<u32> = sorted.iter().map(|x| x.payload).collect();
+                assert_eq!(first_payloads, payloads);
+            }
+        }
+    }
+
+    #[test]
+    fn sort_with_pivot_test() {
+        fn check(pivot: &(impl PivotSelector<i32> + Sync)) {
+            let mut random = Random::new(3);
+            let mut inputs: Vec<Vec<i32>> = [0, 10, 5000, 300_000]
+                .into_iter()
+                .map(|arr_len| random.next_vec(arr_len))
+                .collect();
+            inputs.push(vec![7; 300_000]);
+            for arr in inputs {
+                let mut expected_arr = arr.clone();
+                expected_arr.sort();
+
+                let mut actual = arr.clone();
+                sequential_quicksort_with_pivot(&mut actual, pivot);
+                assert_eq!(expected_arr, actual);
+
+                let mut actual = arr.clone();
+                simple_parallel_quicksort_with_pivot(&mut actual, pivot);
+                assert_eq!(expected_arr, actual);
+
+                #[cfg(feature = "handwritten")]
+                {
+                    let mut actual = arr.clone();
+                    parallel_quicksort_with_pivot(&mut actual, pivot);
+                    assert_eq!(expected_arr, actual);
+                }
+            }
+        }
+
+        check(&LastElement);
+        check(&RandomPivot::new(3));
+        check(&MedianOfThree);
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_depth_limited_test() {
+        let mut random = Random::new(3);
+        for seed in 1..=20 {
+            let pivot = RandomPivot::new(seed);
+            for arr_len in [10, 5000, 100_000] {
+                let mut arr = random.next_vec(arr_len);
+                let mut expected_arr = arr.clone();
+                expected_arr.sort();
+                let max_depth = 4 * arr_len.ilog2() as usize;
+
+                let depth = parallel_quicksort_depth_limited(&mut arr, &pivot, max_depth);
+
+                assert_eq!(expected_arr, arr);
+                assert!(depth < max_depth, "depth {depth} for {arr_len} elements");
+            }
+        }
+
+        // На отсортированных данных последний элемент дает линейную глубину,
+        // но она ограничивается max_depth
+        let mut arr: Vec<i32> = (0..100_000).collect();
+        let expected_arr = arr.clone();
+        assert_eq!(
+            10,
+            parallel_quicksort_depth_limited(&mut arr, &LastElement, 10)
+        );
+        assert_eq!(expected_arr, arr);
+    }
+
+    /// Считает суммарную длину разбиваемых слайсов, то есть work сортировки
+    struct WorkCounting<P> {
+        inner: P,
+        work: AtomicUsize,
+    }
+
+    impl<T, P: PivotSelector<T>> PivotSelector<T> for WorkCounting<P> {
+        fn select(&self, arr: &[T]) -> usize {
+            self.work.fetch_add(arr.len(), Ordering::Relaxed);
+            self.inner.select(arr)
+        }
+    }
+
+    #[test]
+    fn median_of_three_sorted_test() {
+        const ARR_LEN: usize = 5000;
+
+        fn work_on_sorted(inner: impl PivotSelector<i32>) -> usize {
+            let pivot = WorkCounting {
+                inner,
+                work: AtomicUsize::new(0),
+            };
+            let mut arr: Vec<i32> = (0..ARR_LEN as i32).collect();
+            sequential_quicksort_with_pivot(&mut arr, &pivot);
+            assert!(arr.windows(2).all(|w| w[0] <= w[1]));
+            pivot.work.load(Ordering::Relaxed)
+        }
+
+        let n_log_n = ARR_LEN * ARR_LEN.ilog2() as usize;
+        assert!(work_on_sorted(LastElement) > ARR_LEN * ARR_LEN / 4);
+        assert!(work_on_sorted(MedianOfThree) < 2 * n_log_n);
+    }
+
+    #[cfg(feature = "handwritten")]
+    static COMPARISONS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Число, считающее сравнения
+    #[cfg(feature = "handwritten")]
+    #[derive(Clone, Copy, Debug, Default)]
+    struct Counted(i32);
+
+    #[cfg(feature = "handwritten")]
+    impl PartialEq for Counted {
+        fn eq(&self, other: &Self) -> bool {
+            COMPARISONS.fetch_add(1, Ordering::Relaxed);
+            self.0 == other.0
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    impl Eq for Counted {}
+
+    #[cfg(feature = "handwritten")]
+    impl PartialOrd for Counted {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    impl Ord for Counted {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            COMPARISONS.fetch_add(1, Ordering::Relaxed);
+            self.0.cmp(&other.0)
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn rank_test() {
+        let mut random = Random::new(3);
+        for arr_len in [0, 1, 10, 100_000] {
+            let arr = random.next_vec_in_range(arr_len, 0, 1000);
+
+            let mut sorted = arr.clone();
+            sorted.sort();
+            let expected: Vec<usize> = arr
+                .iter()
+                .map(|x| sorted.partition_point(|y| y < x))
+                .collect();
+
+            assert_eq!(expected, parallel_rank(&arr));
+        }
+
+        assert_eq!(vec![2, 0, 2, 0, 4], parallel_rank(&[5, 1, 5, 1, 9]));
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn argsort_test() {
+        let mut random = Random::new(3);
+        let keys_len = 300_000;
+        let inputs = [
+            vec![],
+            vec![5],
+            random.next_vec_in_range(10, 0, 100),
+            random.next_vec_in_range(keys_len, 0, 100),
+            (0..keys_len as i32).collect(),
+            (0..keys_len as i32).rev().collect(),
+            vec![7; keys_len],
+        ];
+        for keys in inputs {
+            let keys_len = keys.len();
+            let perm = parallel_argsort(&keys);
+
+            let mut expected_perm: Vec<usize> = (0..keys_len).collect();
+            expected_perm.sort_by_key(|&i| keys[i]);
+            assert_eq!(expected_perm, perm);
+
+            let sorted_keys = par_gather(&keys, &perm);
+            assert!(sorted_keys.windows(2).all(|w| w[0] <= w[1]));
+
+            let mut expected_keys = keys.clone();
+            expected_keys.sort();
+            assert_eq!(expected_keys, par_apply_permutation(&keys, &perm));
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn sort_chunked_test() {
+        let mut random = Random::new(3);
+        for (arr_len, chunk) in [(0, 10), (1, 1), (100, 7), (100, 1000), (100_003, 1000)] {
+            let mut arr = random.next_vec_in_range(arr_len, -1000, 1000);
+            let mut expected_arr = arr.clone();
+            expected_arr.sort();
+
+            parallel_sort_chunked(&mut arr, chunk);
+
+            assert_eq!(expected_arr, arr);
+        }
+    }
+
+    #[cfg(feature = "handwritten")]
+    #[test]
+    fn select_median_of_medians_test() {
+        let mut random = Random::new(3);
+        for arr_len in [1, 10, 5000, 100_000] {
+            let arr = random.next_vec_in_range(arr_len, -1000, 1000);
+            let mut sorted_arr = arr.clone();
+            sorted_arr.sort();
+
+            for k in [0, arr_len / 3, arr_len - 1] {
+                assert_eq!(
+                    sorted_arr[k],
+                    parallel_select_with_pivot(&arr, k, &MedianOfMedians)
+                );
+            }
+        }
+
+        // На отсортированных данных выбор последнего элемента дает квадратичное время,
+        // а медиана медиан - линейное
+        let arr_len = 100_000;
+        let arr: Vec<Counted> = (0..arr_len as i32).map(Counted).collect();
+        COMPARISONS.store(0, Ordering::Relaxed);
+        assert_eq!(0, parallel_select_with_pivot(&arr, 0, &MedianOfMedians).0);
+        assert!(COMPARISONS.load(Ordering::Relaxed) < 100 * arr_len);
     }
 }
@@ -0,0 +1,235 @@
+#[cfg(feature = "handwritten")]
+use super::{
+    parallel_quicksort_3par_memcpy, parallel_quicksort_par_memcpy, parallel_quicksort_seq_memcpy,
+};
+use super::{rayon_parallel_quicksort, sequential_quicksort, simple_parallel_quicksort};
+use std::fmt;
+use std::str::FromStr;
+
+/// Алгоритм сортировки, выбираемый во время работы программы (например, по имени из аргументов).
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortAlgorithm {
+    /// [sequential_quicksort]
+    Sequential,
+    /// [simple_parallel_quicksort]
+    SimpleParallel,
+    /// [parallel_quicksort_seq_memcpy]
+    #[cfg(feature = "handwritten")]
+    PolylogSeqMemcpy,
+    /// [parallel_quicksort_3par_memcpy]
+    #[cfg(feature = "handwritten")]
+    Polylog3ParMemcpy,
+    /// [parallel_quicksort_par_memcpy]
+    #[cfg(feature = "handwritten")]
+    PolylogParMemcpy,
+    /// [rayon_parallel_quicksort]
+    Rayon,
+}
+
+impl SortAlgorithm {
+    /// Все алгоритмы, доступные при текущем наборе features
+    #[allow(dead_code)]
+    pub fn all() -> Vec<SortAlgorithm> {
+        let mut algorithms = vec![SortAlgorithm::Sequential, SortAlgorithm::SimpleParallel];
+        #[cfg(feature = "handwritten")]
+        algorithms.extend_from_slice(&[
+            SortAlgorithm::PolylogSeqMemcpy,
+            SortAlgorithm::Polylog3ParMemcpy,
+            SortAlgorithm::PolylogParMemcpy,
+        ]);
+        algorithms.push(SortAlgorithm::Rayon);
+        algorithms
+    }
+
+    /// Имя алгоритма, которое принимает [SortAlgorithm::from_str]
+    pub fn name(self) -> &'static str {
+        match self {
+            SortAlgorithm::Sequential => "sequential",
+            SortAlgorithm::SimpleParallel => "simple-parallel",
+            #[cfg(feature = "handwritten")]
+            SortAlgorithm::PolylogSeqMemcpy => "polylog-seq-memcpy",
+            #[cfg(feature = "handwritten")]
+            SortAlgorithm::Polylog3ParMemcpy => "polylog-3par-memcpy",
+            #[cfg(feature = "handwritten")]
+            SortAlgorithm::PolylogParMemcpy => "polylog-par-memcpy",
+            SortAlgorithm::Rayon => "rayon",
+        }
+    }
+}
+
+impl fmt::Display for SortAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Ошибка разбора имени алгоритма: такого алгоритма нет (или он выключен features).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSortAlgorithm(pub String);
+
+impl fmt::Display for UnknownSortAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown sort algorithm: {}", self.0)
+    }
+}
+
+impl FromStr for SortAlgorithm {
+    type Err = UnknownSortAlgorithm;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SortAlgorithm::all()
+            .into_iter()
+            .find(|algorithm| algorithm.name() == s)
+            .ok_or_else(|| UnknownSortAlgorithm(s.to_string()))
+    }
+}
+
+/// Отсортировать массив выбранным алгоритмом.
+#[allow(dead_code)]
+pub fn sort_with(algorithm: SortAlgorithm, arr: &mut [i32]) {
+    match algorithm {
+        SortAlgorithm::Sequential => sequential_quicksort(arr),
+        SortAlgorithm::SimpleParallel => simple_parallel_quicksort(arr),
+        #[cfg(feature = "handwritten")]
+        SortAlgorithm::PolylogSeqMemcpy => parallel_quicksort_seq_memcpy(arr),
+        #[cfg(feature = "handwritten")]
+        SortAlgorithm::Polylog3ParMemcpy => parallel_quicksort_3par_memcpy(arr),
+        #[cfg(feature = "handwritten")]
+        SortAlgorithm::PolylogParMemcpy => parallel_quicksort_par_memcpy(arr),
+        SortAlgorithm::Rayon => rayon_parallel_quicksort(arr),
+    }
+}
+
+/// Массивы не длиннее этого сортируются последовательно:
+/// накладные расходы fork-join на них не окупаются.
+pub const SEQUENTIAL_MAX_LEN: usize = 1 << 14;
+
+/// Массивы не длиннее этого сортируются [simple_parallel_quicksort]: у нее меньше work,
+/// а O(n) span последовательного разбиения на таких размерах еще не заметен.
+/// Более длинные массивы сортируются полилогарифмической [parallel_quicksort_par_memcpy]
+/// (без feature `handwritten` - той же [simple_parallel_quicksort]).
+#[cfg_attr(not(feature = "handwritten"), allow(dead_code))]
+pub const SIMPLE_PARALLEL_MAX_LEN: usize = 1 << 22;
+
+impl SortAlgorithm {
+    /// Выбрать алгоритм по длине массива и количеству потоков (см. [SEQUENTIAL_MAX_LEN]
+    /// и [SIMPLE_PARALLEL_MAX_LEN]). В одном потоке всегда выбирается последовательный.
+    pub fn choose(len: usize, threads: usize) -> SortAlgorithm {
+        if threads <= 1 || len <= SEQUENTIAL_MAX_LEN {
+            return SortAlgorithm::Sequential;
+        }
+        #[cfg(feature = "handwritten")]
+        if len > SIMPLE_PARALLEL_MAX_LEN {
+            return SortAlgorithm::PolylogParMemcpy;
+        }
+        SortAlgorithm::SimpleParallel
+    }
+}
+
+/// Отсортировать массив, автоматически выбрав алгоритм через [SortAlgorithm::choose]
+/// по длине массива и количеству потоков текущего пула rayon.
+/// Все три алгоритма выбирают опорный элемент медианой трех и разбивают массив на три части,
+/// поэтому упорядоченные, развернутые и одинаковые данные не дают квадратичного времени.
+#[allow(dead_code)]
+pub fn parallel_sort<T: Ord + Default + Copy + Send + Sync>(arr: &mut [T]) {
+    match SortAlgorithm::choose(arr.len(), rayon::current_num_threads()) {
+        SortAlgorithm::Sequential => sequential_quicksort(arr),
+        #[cfg(feature = "handwritten")]
+        SortAlgorithm::PolylogParMemcpy => parallel_quicksort_par_memcpy(arr),
+        _ => simple_parallel_quicksort(arr),
+    }
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Random;
+
+    #[test]
+    fn sort_with_test() {
+        let mut random = Random::new(3);
+        for algorithm in SortAlgorithm::all() {
+            for arr_len in [0, 1, 10, 100_000] {
+                let mut arr = random.next_vec(arr_len);
+                let mut expected_arr = arr.clone();
+                expected_arr.sort();
+
+                sort_with(algorithm, &mut arr);
+
+                assert_eq!(expected_arr, arr, "{}", algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn choose_test() {
+        assert_eq!(SortAlgorithm::Sequential, SortAlgorithm::choose(1 << 30, 1));
+        assert_eq!(
+            SortAlgorithm::Sequential,
+            SortAlgorithm::choose(SEQUENTIAL_MAX_LEN, 4)
+        );
+        assert_eq!(
+            SortAlgorithm::SimpleParallel,
+            SortAlgorithm::choose(SEQUENTIAL_MAX_LEN + 1, 4)
+        );
+        assert_eq!(
+            SortAlgorithm::SimpleParallel,
+            SortAlgorithm::choose(SIMPLE_PARALLEL_MAX_LEN, 4)
+        );
+        #[cfg(feature = "handwritten")]
+        assert_eq!(
+            SortAlgorithm::PolylogParMemcpy,
+            SortAlgorithm::choose(SIMPLE_PARALLEL_MAX_LEN + 1, 4)
+        );
+    }
+
+    #[test]
+    fn parallel_sort_test() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let mut random = Random::new(3);
+        for arr_len in [
+            0,
+            1,
+            10,
+            SEQUENTIAL_MAX_LEN,
+            SEQUENTIAL_MAX_LEN + 1,
+            100_000,
+            SIMPLE_PARALLEL_MAX_LEN + 1,
+        ] {
+            let inputs = [
+                ("uniform", random.next_vec(arr_len)),
+                ("sorted", (0..arr_len as i32).collect()),
+                ("reversed", (0..arr_len as i32).rev().collect()),
+                ("all-equal", vec![7; arr_len]),
+            ];
+            for (name, mut arr) in inputs {
+                let mut expected_arr = arr.clone();
+                expected_arr.sort();
+
+                pool.install(|| parallel_sort(&mut arr));
+
+                assert_eq!(expected_arr, arr, "{} input of {} elements", name, arr_len);
+            }
+        }
+    }
+
+    #[test]
+    fn from_str_test() {
+        for algorithm in SortAlgorithm::all() {
+            assert_eq!(Ok(algorithm), algorithm.to_string().parse());
+        }
+        assert_eq!(Ok(SortAlgorithm::Rayon), "rayon".parse());
+        assert_eq!(
+            Err(UnknownSortAlgorithm("bogo".to_string())),
+            "bogo".parse::<SortAlgorithm>()
+        );
+    }
+}
@@ -1,18 +1,26 @@
-use std::cell::UnsafeCell;
+use alloc::vec::Vec;
+#[cfg(feature = "handwritten")]
+use core::cell::UnsafeCell;
 
 ///////////////////
 // UnsafeSlice
 ///////////////////
 
-/// Невладеющая ссылка на слайс с возможностью записи
+/// Невладеющая ссылка на слайс с возможностью записи.
+/// `UnsafeCell<T>` имеет то же представление, что и `T`, поэтому приведение слайса
+/// сохраняет длину, в том числе для типов нулевого размера.
+#[cfg(feature = "handwritten")]
 #[derive(Copy, Clone)]
 pub struct UnsafeSlice<'a, T> {
     slice: &'a [UnsafeCell<T>],
 }
 
+#[cfg(feature = "handwritten")]
 unsafe impl<'a, T: Send + Sync> Send for UnsafeSlice<'a, T> {}
+#[cfg(feature = "handwritten")]
 unsafe impl<'a, T: Send + Sync> Sync for UnsafeSlice<'a, T> {}
 
+#[cfg(feature = "handwritten")]
 impl<'a, T> UnsafeSlice<'a, T> {
     pub fn new(slice: &'a mut [T]) -> Self {
         let ptr = slice as *mut [T] as *const [UnsafeCell<T>];
@@ -21,11 +29,20 @@ impl<'a, T> UnsafeSlice<'a, T> {
         }
     }
 
-    /// SAFETY: нельзя параллельно писать по одному и тому же индексу.
+    /// # Safety
+    ///
+    /// Нельзя параллельно писать по одному и тому же индексу.
     pub unsafe fn write(&self, i: usize, value: T) {
         let ptr = self.slice[i].get();
         *ptr = value;
     }
+
+    /// # Safety
+    ///
+    /// Нельзя параллельно обращаться к индексам `i` и `j` из других задач.
+    pub unsafe fn swap(&self, i: usize, j: usize) {
+        core::ptr::swap(self.slice[i].get(), self.slice[j].get());
+    }
 }
 
 ///////////////////
@@ -45,6 +62,7 @@ impl Random {
     }
 
     /// Xorshift by George Marsaglia
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> u32 {
         self.state ^= self.state << 13;
         self.state ^= self.state >> 17;
@@ -56,12 +74,112 @@ impl Random {
         (self.next() as i64 % (to as i64 - from as i64) + from as i64) as i32
     }
 
-    #[allow(dead_code)]
     pub fn next_vec_in_range(&mut self, len: usize, from: i32, to: i32) -> Vec<i32> {
         (0..len).map(|_| self.next_in_range(from, to)).collect()
     }
 
+    /// Случайное число из [from, to), распределенное равномерно на всем диапазоне `i64`.
+    /// Составляется из двух 32-битных чисел, смещение по модулю устраняется отбрасыванием.
+    pub fn next_i64_in_range(&mut self, from: i64, to: i64) -> i64 {
+        assert!(from < to);
+        let range = to.wrapping_sub(from) as u64;
+        // Наибольшее кратное range число, не превосходящее u64::MAX
+        let zone = u64::MAX - u64::MAX % range;
+        loop {
+            let x = u64::random(self);
+            if x < zone {
+                return from.wrapping_add((x % range) as i64);
+            }
+        }
+    }
+
+    pub fn next_vec_i64_in_range(&mut self, len: usize, from: i64, to: i64) -> Vec<i64> {
+        (0..len).map(|_| self.next_i64_in_range(from, to)).collect()
+    }
+
     pub fn next_vec(&mut self, len: usize) -> Vec<i32> {
         (0..len).map(|_| self.next() as i32).collect()
     }
+
+    /// Заполнить существующий слайс так же, как [Random::next_vec], без выделения памяти.
+    pub fn fill(&mut self, arr: &mut [i32]) {
+        arr.iter_mut().for_each(|x| *x = self.next() as i32);
+    }
+
+    /// Заполнить существующий слайс так же, как [Random::next_vec_in_range], без выделения памяти.
+    pub fn fill_in_range(&mut self, arr: &mut [i32], from: i32, to: i32) {
+        arr.iter_mut()
+            .for_each(|x| *x = self.next_in_range(from, to));
+    }
+
+    pub fn next_vec_of<T: RandomValue>(&mut self, len: usize) -> Vec<T> {
+        (0..len).map(|_| T::random(self)).collect()
+    }
+}
+
+/// Тип, значения которого можно сгенерировать с помощью [Random]
+pub trait RandomValue {
+    fn random(random: &mut Random) -> Self;
+}
+
+impl RandomValue for u32 {
+    fn random(random: &mut Random) -> Self {
+        random.next()
+    }
+}
+
+impl RandomValue for i32 {
+    fn random(random: &mut Random) -> Self {
+        random.next() as i32
+    }
+}
+
+impl RandomValue for u64 {
+    fn random(random: &mut Random) -> Self {
+        ((random.next() as u64) << 32) | random.next() as u64
+    }
+}
+
+impl RandomValue for i64 {
+    fn random(random: &mut Random) -> Self {
+        u64::random(random) as i64
+    }
+}
+
+///////////////////
+// Tests
+///////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_vec_i64_in_range_test() {
+        let mut random = Random::new(3);
+        for (from, to) in [
+            (0, 1),
+            (-5, 5),
+            (i64::MIN, i64::MAX),
+            (i64::MIN, 0),
+            (i64::MAX - 10, i64::MAX),
+        ] {
+            let arr = random.next_vec_i64_in_range(10_000, from, to);
+            assert!(arr.iter().all(|&x| from <= x && x < to));
+        }
+
+        let arr = random.next_vec_i64_in_range(10_000, i64::MIN, i64::MAX);
+        assert!(arr.iter().any(|&x| x < i64::MIN / 2));
+        assert!(arr.iter().any(|&x| x > i64::MAX / 2));
+    }
+
+    #[test]
+    fn fill_test() {
+        let mut arr = vec![0; 1000];
+        Random::new(3).fill(&mut arr);
+        assert_eq!(Random::new(3).next_vec(1000), arr);
+
+        Random::new(5).fill_in_range(&mut arr, -10, 10);
+        assert_eq!(Random::new(5).next_vec_in_range(1000, -10, 10), arr);
+    }
 }
@@ -0,0 +1,21 @@
+//! Библиотека (utils, parallel_primitives) должна собираться без `std`.
+
+use std::process::Command;
+
+#[test]
+fn builds_without_std() {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--lib", "--no-default-features"])
+        .args(["--features", "handwritten,log-span-scan"])
+        .args([
+            "--manifest-path",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"),
+        ])
+        .args([
+            "--target-dir",
+            concat!(env!("CARGO_TARGET_TMPDIR"), "/no_std"),
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+}